@@ -0,0 +1,332 @@
+// Helpers fail with the `Status` returned by the RPCs, large but not worth boxing.
+#![allow(clippy::result_large_err)]
+
+use std::sync::Arc;
+
+use chrono::{Datelike, Duration, NaiveDate, TimeZone};
+use tonic::{Request, Response, Status};
+
+use crate::calendars::Calendars;
+use crate::scheduler::Scheduler;
+use crate::working_days::WorkingDays;
+
+tonic::include_proto!("calendar");
+
+#[derive(Debug)]
+pub struct CalendarHandler {
+    pub calendars: Arc<Calendars>,
+    pub scheduler: Arc<Scheduler>,
+}
+
+#[tonic::async_trait]
+impl calendar_server::Calendar for CalendarHandler {
+    async fn nth_working_day_date(
+        &self,
+        request: Request<NthWorkingDayDateRequest>,
+    ) -> Result<Response<NthWorkingDayDateResponse>, Status> {
+        let message = request.into_inner();
+
+        let first_day =
+            NaiveDate::from_ymd_opt(message.year, message.month, 1).ok_or_else(|| {
+                Status::invalid_argument("Field `month` should be a value between 1 and 12.")
+            })?;
+        let working_days = read_calendar(&self.calendars, &message.calendar, first_day)?;
+
+        let nth_working_day = i8::try_from(message.nth_working_day)
+            .ok()
+            .filter(|n| *n != 0 && (-31..=31).contains(n))
+            .ok_or_else(|| {
+                Status::invalid_argument(
+                    "Field `nthWorkingDay` should be a value between -31 and 31, except 0.",
+                )
+            })?;
+
+        check_month_in_range(&working_days, first_day)?;
+
+        let date = working_days
+            .nth_working_day_date(message.year, message.month, nth_working_day)
+            .map(|date| date.format("%Y-%m-%d").to_string());
+
+        Ok(Response::new(NthWorkingDayDateResponse { date }))
+    }
+
+    async fn is_working_day(
+        &self,
+        request: Request<IsWorkingDayRequest>,
+    ) -> Result<Response<IsWorkingDayResponse>, Status> {
+        let message = request.into_inner();
+
+        let date = NaiveDate::parse_from_str(&message.date, "%Y-%m-%d").map_err(|_| {
+            Status::invalid_argument("Field `date` should be a date formatted as `%Y-%m-%d`.")
+        })?;
+        let working_days = read_calendar(&self.calendars, &message.calendar, date)?;
+        let date = working_days.time_offset.from_utc_date(&date);
+
+        let working_day = working_days
+            .is_working_day(date)
+            .map_err(|err| Status::out_of_range(err.to_string()))?;
+
+        Ok(Response::new(IsWorkingDayResponse {
+            working_day,
+            weekend: working_days.weekend.contains(date.weekday()),
+            holiday: working_days.holiday_name(date).map(str::to_string),
+        }))
+    }
+
+    async fn list_schedules(
+        &self,
+        _request: Request<ListSchedulesRequest>,
+    ) -> Result<Response<ListSchedulesResponse>, Status> {
+        let mut subscribers = self.scheduler.subscribers();
+        subscribers.sort();
+        let schedules = subscribers
+            .into_iter()
+            .map(|(key, subscribers)| Schedule {
+                metadata: key.metadata().clone().into_iter().collect(),
+                subscribers: subscribers as u32,
+            })
+            .collect();
+
+        Ok(Response::new(ListSchedulesResponse { schedules }))
+    }
+}
+
+fn read_calendar(
+    calendars: &Calendars,
+    name: &str,
+    date: NaiveDate,
+) -> Result<Arc<WorkingDays>, Status> {
+    let name = if name.is_empty() { None } else { Some(name) };
+    calendars
+        .get_covering(name, date, Duration::zero())
+        .map_err(|err| Status::invalid_argument(err.to_string()))
+}
+
+fn check_month_in_range(working_days: &WorkingDays, first_day: NaiveDate) -> Result<(), Status> {
+    working_days
+        .working_days_mtd(working_days.time_offset.from_utc_date(&first_day))
+        .map(|_| ())
+        .map_err(|err| Status::out_of_range(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use chrono::{FixedOffset, TimeZone};
+    use tonic::{Code, Request};
+
+    use crate::calendar_handler::calendar_server::Calendar;
+    use crate::calendar_handler::{
+        CalendarHandler, IsWorkingDayRequest, ListSchedulesRequest, NthWorkingDayDateRequest,
+        Schedule,
+    };
+    use crate::calendars::Calendars;
+    use crate::handler::ScaledObjectRef;
+    use crate::holiday::Holiday;
+    use crate::profiles::Profiles;
+    use crate::scheduler::Scheduler;
+    use crate::working_days::{Weekend, WorkingDays};
+
+    #[tokio::test]
+    async fn should_return_the_date_of_the_nth_working_day() {
+        let handler = simple_handler();
+
+        let result = handler
+            .nth_working_day_date(Request::new(NthWorkingDayDateRequest {
+                calendar: String::new(),
+                year: 2022,
+                month: 11,
+                nth_working_day: 2,
+            }))
+            .await;
+
+        assert_eq!(
+            result.unwrap().into_inner().date,
+            Some("2022-11-03".to_string())
+        );
+
+        let result = handler
+            .nth_working_day_date(Request::new(NthWorkingDayDateRequest {
+                calendar: "default".to_string(),
+                year: 2022,
+                month: 11,
+                nth_working_day: -1,
+            }))
+            .await;
+
+        assert_eq!(
+            result.unwrap().into_inner().date,
+            Some("2022-11-30".to_string())
+        );
+
+        let result = handler
+            .nth_working_day_date(Request::new(NthWorkingDayDateRequest {
+                calendar: String::new(),
+                year: 2022,
+                month: 11,
+                nth_working_day: 31,
+            }))
+            .await;
+
+        assert_eq!(result.unwrap().into_inner().date, None);
+    }
+
+    #[tokio::test]
+    async fn should_require_valid_arguments() {
+        let handler = simple_handler();
+
+        let result = handler
+            .nth_working_day_date(Request::new(NthWorkingDayDateRequest {
+                calendar: "us".to_string(),
+                year: 2022,
+                month: 11,
+                nth_working_day: 1,
+            }))
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().message().to_string(),
+            "Unknown calendar `us`."
+        );
+
+        let result = handler
+            .nth_working_day_date(Request::new(NthWorkingDayDateRequest {
+                calendar: String::new(),
+                year: 2022,
+                month: 13,
+                nth_working_day: 1,
+            }))
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().message().to_string(),
+            "Field `month` should be a value between 1 and 12."
+        );
+
+        let result = handler
+            .nth_working_day_date(Request::new(NthWorkingDayDateRequest {
+                calendar: String::new(),
+                year: 2022,
+                month: 11,
+                nth_working_day: 0,
+            }))
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().message().to_string(),
+            "Field `nthWorkingDay` should be a value between -31 and 31, except 0."
+        );
+
+        let result = handler
+            .nth_working_day_date(Request::new(NthWorkingDayDateRequest {
+                calendar: String::new(),
+                year: 2023,
+                month: 1,
+                nth_working_day: 1,
+            }))
+            .await;
+
+        assert!(result.is_err());
+        let status = result.unwrap_err();
+        assert_eq!(status.code(), Code::OutOfRange);
+        assert_eq!(status.message().to_string(), "The requested date was not calculated. Table processed for dates between 2022-01-01-03:00 and 2022-12-31-03:00.");
+    }
+
+    #[tokio::test]
+    async fn should_explain_why_a_date_is_not_a_working_day() {
+        let handler = simple_handler();
+
+        let is_working_day = |date: &str| {
+            handler.is_working_day(Request::new(IsWorkingDayRequest {
+                calendar: String::new(),
+                date: date.to_string(),
+            }))
+        };
+
+        let response = is_working_day("2022-11-15").await.unwrap().into_inner();
+        assert!(!response.working_day);
+        assert!(!response.weekend);
+        assert_eq!(
+            response.holiday,
+            Some("Proclamação da República".to_string())
+        );
+
+        let response = is_working_day("2022-11-02").await.unwrap().into_inner();
+        assert!(!response.working_day);
+        assert_eq!(response.holiday, None);
+
+        let response = is_working_day("2022-11-19").await.unwrap().into_inner();
+        assert!(!response.working_day);
+        assert!(response.weekend);
+
+        let response = is_working_day("2022-11-16").await.unwrap().into_inner();
+        assert!(response.working_day);
+
+        let result = is_working_day("16/11/2022").await;
+        assert_eq!(
+            result.unwrap_err().message().to_string(),
+            "Field `date` should be a date formatted as `%Y-%m-%d`."
+        );
+
+        let result = is_working_day("2023-01-02").await;
+        assert_eq!(result.unwrap_err().code(), Code::OutOfRange);
+    }
+
+    #[tokio::test]
+    async fn should_list_schedules_and_subscribers() {
+        let handler = simple_handler();
+        let mut metadata = HashMap::new();
+        metadata.insert("nthWorkingDay".to_string(), "5".to_string());
+        metadata.insert("fromTime".to_string(), "06:00:00".to_string());
+        metadata.insert("toTime".to_string(), "18:00:00".to_string());
+        metadata.insert("targetSize".to_string(), "10".to_string());
+        let message = ScaledObjectRef {
+            name: "name".to_string(),
+            namespace: "namespace".to_string(),
+            scaler_metadata: metadata.clone(),
+        };
+
+        let _first = handler.scheduler.subscribe(message.clone());
+        let _second = handler.scheduler.subscribe(message);
+
+        let result = handler
+            .list_schedules(Request::new(ListSchedulesRequest {}))
+            .await;
+        assert_eq!(
+            result.unwrap().into_inner().schedules,
+            vec![Schedule {
+                metadata,
+                subscribers: 2
+            }]
+        );
+    }
+
+    fn simple_handler() -> CalendarHandler {
+        let calendars = simple_calendars();
+        let scheduler = Scheduler::new(Arc::clone(&calendars), Arc::new(Profiles::default()), 0);
+        CalendarHandler {
+            calendars,
+            scheduler: Arc::new(scheduler),
+        }
+    }
+
+    fn simple_calendars() -> Arc<Calendars> {
+        let mut holidays = Vec::new();
+        let offset = FixedOffset::west(3 * 3600);
+
+        holidays.push(offset.ymd(2022, 11, 2).into());
+        holidays.push(
+            Holiday::from(offset.ymd(2022, 11, 15))
+                .with_name(Some("Proclamação da República".to_string())),
+        );
+
+        Arc::new(Calendars::single(
+            WorkingDays::build(offset, Weekend::default(), holidays).unwrap(),
+        ))
+    }
+}
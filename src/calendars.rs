@@ -0,0 +1,701 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::sync::{Arc, RwLock};
+
+use chrono::{Datelike, Duration, NaiveDate, TimeZone, Utc};
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use crate::calendars::CalendarsError::{
+    ErrorReadingConfig, InvalidComposition, InvalidConfig, InvalidObservance, InvalidTimeZone,
+    InvalidWeekend, LoadingHolidays, MissingHolidaysSource, NoCalendarConfigured,
+    NoCalendarSelected, ProcessingWorkingDays, UnknownCalendar, UnknownComposedCalendar,
+    UnknownDefaultCalendar,
+};
+use crate::holiday;
+use crate::holiday::Observance;
+use crate::holidays_loader;
+use crate::holidays_loader::{HolidaysFormat, HolidaysLoaderError, LoadMode, LoadedHolidays};
+use crate::time_zone;
+use crate::time_zone::CalendarTimeZone;
+use crate::working_days::WorkingDaysError::DateOutOfRange;
+use crate::working_days::{Composition, Weekend, WorkingDays, WorkingDaysError};
+
+pub const DEFAULT_CALENDAR: &str = "default";
+
+/// Years before or after the current one calendars with holiday rules may be extended to.
+pub const MAX_EXTENSION_YEARS: i32 = 10;
+
+#[derive(Debug)]
+pub struct Calendars {
+    default: Option<String>,
+    tables: HashMap<String, RwLock<Arc<WorkingDays>>>,
+    /// Calendars each composed calendar is built from, composed again when they are extended.
+    compositions: RwLock<HashMap<String, (Composition, Vec<String>)>>,
+    reloaded: watch::Sender<()>,
+}
+
+/// Settings shared by the holidays of a calendar. Calendars of a calendars file use the given
+/// ones unless they configure their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CalendarSettings {
+    pub time_zone: CalendarTimeZone,
+    pub weekend: Weekend,
+    pub observance: Observance,
+    pub load_mode: LoadMode,
+    /// Format of the holidays files, given by their extension when `None`.
+    pub holidays_format: Option<HolidaysFormat>,
+}
+
+#[derive(Error, Debug)]
+pub enum CalendarsError {
+    #[error("Error reading calendars file {0}.")]
+    ErrorReadingConfig(String, #[source] std::io::Error),
+    #[error("Invalid calendars file {0}.")]
+    InvalidConfig(String, #[source] toml::de::Error),
+    #[error("The calendars file {0} does not configure any calendar.")]
+    NoCalendarConfigured(String),
+    #[error("The default calendar `{0}` is not configured.")]
+    UnknownDefaultCalendar(String),
+    #[error("Invalid weekend of calendar `{0}`.")]
+    InvalidWeekend(String, #[source] WorkingDaysError),
+    #[error("Invalid observance of calendar `{0}`.")]
+    InvalidObservance(String, #[source] holiday::InvalidObservance),
+    #[error("Invalid time zone of calendar `{0}`.")]
+    InvalidTimeZone(String, #[source] time_zone::InvalidTimeZone),
+    #[error("Calendar `{0}` needs a `holidays_file` or a `rules_file`.")]
+    MissingHolidaysSource(String),
+    #[error("Calendar `{0}` should either have its own holidays or be a `union` or an `intersection` of other calendars.")]
+    InvalidComposition(String),
+    #[error("Calendar `{0}` composes `{1}`, which is not a calendar with its own holidays.")]
+    UnknownComposedCalendar(String, String),
+    #[error("Error loading holidays of calendar `{0}`.")]
+    LoadingHolidays(String, #[source] HolidaysLoaderError),
+    #[error("Error processing working days of calendar `{0}`.")]
+    ProcessingWorkingDays(String, #[source] WorkingDaysError),
+    #[error("Unknown calendar `{0}`.")]
+    UnknownCalendar(String),
+    #[error("No calendar selected and no default calendar configured.")]
+    NoCalendarSelected,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct CalendarsConfig {
+    default: Option<String>,
+    calendars: BTreeMap<String, CalendarConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct CalendarConfig {
+    holidays_file: Option<String>,
+    rules_file: Option<String>,
+    weekend: Option<String>,
+    observance: Option<String>,
+    time_zone: Option<String>,
+    union: Option<Vec<String>>,
+    intersection: Option<Vec<String>>,
+}
+
+impl CalendarConfig {
+    fn composition(&self, name: &str) -> Result<Option<(Composition, &[String])>, CalendarsError> {
+        let composition = match (&self.union, &self.intersection) {
+            (None, None) => return Ok(None),
+            (Some(calendars), None) => (Composition::Union, calendars.as_slice()),
+            (None, Some(calendars)) => (Composition::Intersection, calendars.as_slice()),
+            (Some(_), Some(_)) => return Err(InvalidComposition(name.to_string())),
+        };
+
+        if self.holidays_file.is_some()
+            || self.rules_file.is_some()
+            || self.weekend.is_some()
+            || self.observance.is_some()
+            || self.time_zone.is_some()
+        {
+            return Err(InvalidComposition(name.to_string()));
+        }
+
+        Ok(Some(composition))
+    }
+}
+
+impl Calendars {
+    pub fn new(default: Option<String>, tables: HashMap<String, Arc<WorkingDays>>) -> Self {
+        let tables = tables
+            .into_iter()
+            .map(|(name, working_days)| (name, RwLock::new(working_days)))
+            .collect();
+
+        let (reloaded, _) = watch::channel(());
+        Calendars {
+            default,
+            tables,
+            compositions: RwLock::new(HashMap::new()),
+            reloaded,
+        }
+    }
+
+    pub fn with_compositions(
+        self,
+        compositions: HashMap<String, (Composition, Vec<String>)>,
+    ) -> Self {
+        *self.compositions.write().unwrap() = compositions;
+        self
+    }
+
+    /// Registry with a single calendar, used as default.
+    pub fn single(working_days: WorkingDays) -> Self {
+        let mut tables = HashMap::new();
+        tables.insert(DEFAULT_CALENDAR.to_string(), Arc::new(working_days));
+
+        Self::new(Some(DEFAULT_CALENDAR.to_string()), tables)
+    }
+
+    /// Loads every calendar listed in a TOML calendars file. Composed calendars are built once
+    /// the calendars they compose are loaded.
+    pub fn load(settings: CalendarSettings, calendars_file: &str) -> Result<Self, CalendarsError> {
+        let content = fs::read_to_string(calendars_file)
+            .map_err(|err| ErrorReadingConfig(calendars_file.to_string(), err))?;
+        let config: CalendarsConfig = toml::from_str(&content)
+            .map_err(|err| InvalidConfig(calendars_file.to_string(), err))?;
+
+        if config.calendars.is_empty() {
+            return Err(NoCalendarConfigured(calendars_file.to_string()));
+        }
+
+        if let Some(default) = &config.default {
+            if !config.calendars.contains_key(default) {
+                return Err(UnknownDefaultCalendar(default.clone()));
+            }
+        }
+
+        let mut tables = HashMap::new();
+        let mut composed = Vec::new();
+        for (name, calendar) in &config.calendars {
+            if let Some(composition) = calendar.composition(name)? {
+                composed.push((name, composition));
+                continue;
+            }
+
+            let mut settings = settings;
+            if let Some(value) = &calendar.weekend {
+                settings.weekend = value
+                    .parse::<Weekend>()
+                    .map_err(|err| InvalidWeekend(name.to_string(), err))?;
+            }
+            if let Some(value) = &calendar.observance {
+                settings.observance = value
+                    .parse::<Observance>()
+                    .map_err(|err| InvalidObservance(name.to_string(), err))?;
+            }
+            if let Some(value) = &calendar.time_zone {
+                settings.time_zone = value
+                    .parse::<CalendarTimeZone>()
+                    .map_err(|err| InvalidTimeZone(name.to_string(), err))?;
+            }
+
+            let working_days = build_calendar(
+                name,
+                settings,
+                calendar.holidays_file.as_deref(),
+                calendar.rules_file.as_deref(),
+            )?;
+
+            info!(
+                "Calendar `{}` loaded with time zone {}, weekend {} and working days MTD between {} and {}.",
+                name,
+                working_days.time_zone,
+                working_days.weekend,
+                working_days.start_date,
+                working_days.end_date
+            );
+            tables.insert(name.clone(), Arc::new(working_days));
+        }
+
+        let mut composed_tables = Vec::new();
+        let mut compositions = HashMap::new();
+        for (name, (composition, calendar_names)) in composed {
+            let calendars = calendar_names
+                .iter()
+                .map(|calendar_name| {
+                    tables
+                        .get(calendar_name)
+                        .map(Arc::as_ref)
+                        .ok_or_else(|| UnknownComposedCalendar(name.clone(), calendar_name.clone()))
+                })
+                .collect::<Result<Vec<&WorkingDays>, CalendarsError>>()?;
+            let working_days = WorkingDays::compose(composition, &calendars)
+                .map_err(|err| ProcessingWorkingDays(name.clone(), err))?;
+
+            info!(
+                "Calendar `{}` composed from {} with weekend {} and working days MTD between {} and {}.",
+                name,
+                calendar_names.join(", "),
+                working_days.weekend,
+                working_days.start_date,
+                working_days.end_date
+            );
+            composed_tables.push((name.clone(), Arc::new(working_days)));
+            compositions.insert(name.clone(), (composition, calendar_names.to_vec()));
+        }
+        tables.extend(composed_tables);
+
+        Ok(Self::new(config.default, tables).with_compositions(compositions))
+    }
+
+    /// Returns the named calendar, or the default one when no name is given.
+    pub fn get(&self, name: Option<&str>) -> Result<Arc<WorkingDays>, CalendarsError> {
+        let (_, table) = self.table(name)?;
+        let working_days = table.read().unwrap();
+        Ok(Arc::clone(&working_days))
+    }
+
+    /// Same as `get`, but calendars with holiday rules are first extended to cover the date, and
+    /// `margin` before and after it, in any time offset. They are only extended up to `MAX_EXTENSION_YEARS` around the current
+    /// year. Composed calendars are composed again once the calendars they compose are extended.
+    pub fn get_covering(
+        &self,
+        name: Option<&str>,
+        date: NaiveDate,
+        margin: Duration,
+    ) -> Result<Arc<WorkingDays>, CalendarsError> {
+        let (name, table) = self.table(name)?;
+        let working_days = Arc::clone(&table.read().unwrap());
+        let covers = |working_days: &WorkingDays, date: Option<NaiveDate>| {
+            date.is_some_and(|date| {
+                working_days.covers(working_days.time_offset.from_utc_date(&date))
+            })
+        };
+        // A day more to cover the date in any time offset
+        let span = margin + Duration::days(1);
+        let dates = [date.checked_sub_signed(span), date.checked_add_signed(span)];
+
+        if dates.iter().all(|date| covers(&working_days, *date)) {
+            return Ok(working_days);
+        }
+        let composition = self.compositions.read().unwrap().get(name).cloned();
+        if let Some((composition, calendar_names)) = composition {
+            return self.compose_covering(name, table, composition, &calendar_names, date, margin);
+        }
+        if !working_days.is_extendable() {
+            return Ok(working_days);
+        }
+
+        let year = Utc::now().year();
+        if (date.year() - year).abs() > MAX_EXTENSION_YEARS {
+            return Err(ProcessingWorkingDays(
+                name.to_string(),
+                DateOutOfRange(working_days.start_date, working_days.end_date),
+            ));
+        }
+        let dates: Vec<_> = dates
+            .into_iter()
+            .flatten()
+            .map(|date| working_days.time_offset.from_utc_date(&date))
+            .collect();
+
+        let mut working_days = table.write().unwrap();
+        if !dates.iter().all(|date| working_days.covers(*date)) {
+            let mut extended = WorkingDays::clone(&working_days);
+            for date in dates {
+                extended
+                    .extend_to(date)
+                    .map_err(|err| ProcessingWorkingDays(name.to_string(), err))?;
+            }
+
+            info!(
+                "Calendar `{}` extended to working days MTD between {} and {}.",
+                name, extended.start_date, extended.end_date
+            );
+            *working_days = Arc::new(extended);
+        }
+
+        Ok(Arc::clone(&working_days))
+    }
+
+    fn compose_covering(
+        &self,
+        name: &str,
+        table: &RwLock<Arc<WorkingDays>>,
+        composition: Composition,
+        calendar_names: &[String],
+        date: NaiveDate,
+        margin: Duration,
+    ) -> Result<Arc<WorkingDays>, CalendarsError> {
+        let calendars = calendar_names
+            .iter()
+            .map(|calendar_name| self.get_covering(Some(calendar_name), date, margin))
+            .collect::<Result<Vec<Arc<WorkingDays>>, CalendarsError>>()?;
+        let current = Arc::clone(&table.read().unwrap());
+        // The composed table covers the range shared by every calendar
+        let start = calendars
+            .iter()
+            .map(|calendar| calendar.start_date.naive_local())
+            .max();
+        let end = calendars
+            .iter()
+            .map(|calendar| calendar.end_date.naive_local())
+            .min();
+        if start == Some(current.start_date.naive_local())
+            && end == Some(current.end_date.naive_local())
+        {
+            return Ok(current);
+        }
+
+        let calendars: Vec<&WorkingDays> = calendars.iter().map(Arc::as_ref).collect();
+        let composed = WorkingDays::compose(composition, &calendars)
+            .map_err(|err| ProcessingWorkingDays(name.to_string(), err))?;
+        info!(
+            "Calendar `{}` composed again with working days MTD between {} and {}.",
+            name, composed.start_date, composed.end_date
+        );
+
+        let composed = Arc::new(composed);
+        *table.write().unwrap() = Arc::clone(&composed);
+        Ok(composed)
+    }
+
+    fn table<'a>(
+        &'a self,
+        name: Option<&'a str>,
+    ) -> Result<(&'a str, &'a RwLock<Arc<WorkingDays>>), CalendarsError> {
+        match name.or(self.default.as_deref()) {
+            None => Err(NoCalendarSelected),
+            Some(name) => self
+                .tables
+                .get(name)
+                .map(|table| (name, table))
+                .ok_or_else(|| UnknownCalendar(name.to_string())),
+        }
+    }
+
+    /// Replaces the tables of the calendars also found in the reloaded ones. Calendars added or
+    /// removed since the start are ignored.
+    pub fn swap(&self, reloaded: Calendars) {
+        for (name, table) in reloaded.tables {
+            match self.tables.get(&name) {
+                Some(current) => *current.write().unwrap() = table.into_inner().unwrap(),
+                None => warn!(
+                    "Calendar `{}` is new and needs a restart to be served.",
+                    name
+                ),
+            }
+        }
+        *self.compositions.write().unwrap() = reloaded.compositions.into_inner().unwrap();
+        self.reloaded.send_replace(());
+    }
+
+    /// Notified every time the calendars are swapped.
+    pub fn subscribe_reloads(&self) -> watch::Receiver<()> {
+        self.reloaded.subscribe()
+    }
+
+    pub fn default_name(&self) -> Option<&str> {
+        self.default.as_deref()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+}
+
+/// Builds a calendar from a holidays CSV, a holiday rules CSV or both. Calendars with rules
+/// start covering the previous, the current and the next year.
+pub fn build_calendar(
+    name: &str,
+    settings: CalendarSettings,
+    holidays_file: Option<&str>,
+    rules_file: Option<&str>,
+) -> Result<WorkingDays, CalendarsError> {
+    let time_offset = settings.time_zone.date_offset();
+    let mut loaded = LoadedHolidays::default();
+    if let Some(holidays_file) = holidays_file {
+        loaded = holidays_loader::load_with_format(
+            settings
+                .holidays_format
+                .unwrap_or_else(|| HolidaysFormat::of(holidays_file)),
+            time_offset,
+            holidays_file,
+            settings.observance,
+            settings.load_mode,
+        )
+        .map_err(|err| LoadingHolidays(name.to_string(), err))?;
+        info!(
+            "Loaded {} holidays from {}.",
+            loaded.holidays.len(),
+            holidays_file
+        );
+    }
+    if let Some(rules_file) = rules_file {
+        let rules =
+            holidays_loader::load_rules(rules_file, settings.observance, settings.load_mode)
+                .map_err(|err| LoadingHolidays(name.to_string(), err))?;
+        info!("Loaded {} holiday rules from {}.", rules.len(), rules_file);
+        loaded.rules.extend(rules);
+    }
+
+    let working_days = match (holidays_file, rules_file) {
+        (None, None) => Err(MissingHolidaysSource(name.to_string())),
+        (Some(_), None) if loaded.rules.is_empty() => {
+            WorkingDays::build(time_offset, settings.weekend, loaded.holidays)
+                .map_err(|err| ProcessingWorkingDays(name.to_string(), err))
+        }
+        _ => {
+            let year = Utc::now().year();
+            Ok(WorkingDays::build_with_rules(
+                time_offset,
+                settings.weekend,
+                loaded.holidays,
+                loaded.rules,
+                year - 1,
+                year + 1,
+            ))
+        }
+    }?;
+
+    Ok(working_days.with_time_zone(settings.time_zone))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, TimeZone, Weekday};
+
+    use super::*;
+
+    #[test]
+    fn should_load_every_configured_calendar() {
+        let offset = FixedOffset::west(3 * 3600);
+
+        let calendars =
+            Calendars::load(settings(offset), "tests_resources/calendars.toml").unwrap();
+        assert_eq!(calendars.len(), 8);
+        assert_eq!(calendars.default_name(), Some("br"));
+
+        let br = calendars.get(Some("br")).unwrap();
+        assert_eq!(br.working_days_mtd(offset.ymd(2020, 4, 10)).unwrap(), 7);
+
+        let other = calendars.get(Some("other")).unwrap();
+        assert_eq!(other.working_days_mtd(offset.ymd(2020, 4, 10)).unwrap(), 8);
+
+        let default = calendars.get(None).unwrap();
+        assert_eq!(default.start_date, br.start_date);
+
+        let middle_east = calendars.get(Some("middle_east")).unwrap();
+        assert_eq!(
+            middle_east.time_zone,
+            CalendarTimeZone::Named(chrono_tz::Asia::Dubai)
+        );
+        assert_eq!(br.time_zone, CalendarTimeZone::Fixed(offset));
+        assert_eq!(
+            middle_east.weekend,
+            Weekend::new(&[Weekday::Fri, Weekday::Sat])
+        );
+        assert_eq!(
+            middle_east
+                .working_days_mtd(offset.ymd(2020, 4, 10))
+                .unwrap(),
+            7
+        );
+    }
+
+    #[test]
+    fn should_compose_calendars() {
+        let offset = FixedOffset::west(3 * 3600);
+
+        let calendars =
+            Calendars::load(settings(offset), "tests_resources/calendars.toml").unwrap();
+
+        // 2020-04-10 is a holiday only in `br`
+        let both_open = calendars.get(Some("both_open")).unwrap();
+        assert_eq!(
+            both_open.working_days_mtd(offset.ymd(2020, 4, 10)).unwrap(),
+            7
+        );
+        assert!(!both_open.is_extendable());
+
+        let any_open = calendars.get(Some("any_open")).unwrap();
+        assert_eq!(
+            any_open.working_days_mtd(offset.ymd(2020, 4, 10)).unwrap(),
+            8
+        );
+        assert!(!any_open.is_working_day(offset.ymd(2020, 1, 1)).unwrap());
+
+        let result = Calendars::load(
+            settings(offset),
+            "tests_resources/invalid_composed_calendars.toml",
+        );
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Calendar `both_open` composes `us`, which is not a calendar with its own holidays."
+        );
+    }
+
+    #[test]
+    fn should_extend_calendars_with_rules_on_demand() {
+        let offset = FixedOffset::west(3 * 3600);
+
+        let calendars =
+            Calendars::load(settings(offset), "tests_resources/calendars.toml").unwrap();
+
+        let year = Utc::now().year();
+        let rules = calendars.get(Some("rules")).unwrap();
+        assert_eq!(rules.start_date, offset.ymd(year - 1, 1, 1));
+        assert_eq!(rules.end_date, offset.ymd(year + 1, 12, 31));
+
+        let rules = calendars
+            .get_covering(
+                Some("rules"),
+                NaiveDate::from_ymd(year + 5, 12, 31),
+                Duration::zero(),
+            )
+            .unwrap();
+        assert_eq!(rules.end_date, offset.ymd(year + 6, 12, 31));
+        assert!(!rules.is_working_day(offset.ymd(year + 5, 12, 25)).unwrap());
+        assert_eq!(
+            calendars.get(Some("rules")).unwrap().end_date,
+            offset.ymd(year + 6, 12, 31)
+        );
+
+        let br = calendars
+            .get_covering(
+                Some("br"),
+                NaiveDate::from_ymd(year + 5, 12, 31),
+                Duration::zero(),
+            )
+            .unwrap();
+        assert_eq!(br.end_date, offset.ymd(2020, 12, 31));
+
+        for date in [
+            NaiveDate::from_ymd(year + 11, 1, 1),
+            NaiveDate::MAX,
+            NaiveDate::MIN,
+        ] {
+            let result = calendars.get_covering(Some("rules"), date, Duration::zero());
+            assert!(matches!(
+                result.unwrap_err(),
+                ProcessingWorkingDays(_, DateOutOfRange(_, _))
+            ));
+        }
+        assert_eq!(
+            calendars.get(Some("rules")).unwrap().end_date,
+            offset.ymd(year + 6, 12, 31)
+        );
+    }
+
+    #[test]
+    fn should_compose_again_calendars_extended_on_demand() {
+        let offset = FixedOffset::west(3 * 3600);
+
+        let calendars =
+            Calendars::load(settings(offset), "tests_resources/calendars.toml").unwrap();
+
+        let year = Utc::now().year();
+        let both_open = calendars.get(Some("rules_both_open")).unwrap();
+        assert_eq!(both_open.end_date, offset.ymd(year + 1, 12, 31));
+
+        let both_open = calendars
+            .get_covering(
+                Some("rules_both_open"),
+                NaiveDate::from_ymd(year + 5, 12, 31),
+                Duration::zero(),
+            )
+            .unwrap();
+        assert_eq!(both_open.end_date, offset.ymd(year + 6, 12, 31));
+        assert!(!both_open
+            .is_working_day(offset.ymd(year + 5, 12, 25))
+            .unwrap());
+        let friday = NaiveDate::from_weekday_of_month(year + 5, 12, Weekday::Fri, 1);
+        let friday = offset.from_utc_date(&friday);
+        assert!(!both_open.is_working_day(friday).unwrap());
+        assert_eq!(
+            calendars.get(Some("rules_both_open")).unwrap().end_date,
+            offset.ymd(year + 6, 12, 31)
+        );
+    }
+
+    #[test]
+    fn should_extend_ics_calendars_with_recurring_events() {
+        let offset = FixedOffset::west(3 * 3600);
+
+        let mut working_days = build_calendar(
+            "br",
+            settings(offset),
+            Some("tests_resources/holidays.ics"),
+            None,
+        )
+        .unwrap();
+        assert!(working_days.is_extendable());
+
+        let year = Utc::now().year();
+        working_days.extend_to(offset.ymd(year + 5, 1, 1)).unwrap();
+        assert!(!working_days.is_working_day(offset.ymd(2022, 3, 1)).unwrap());
+        assert!(!working_days
+            .is_working_day(offset.ymd(year + 5, 12, 25))
+            .unwrap());
+    }
+
+    #[test]
+    fn should_return_error_if_calendar_is_unknown() {
+        let offset = FixedOffset::west(3 * 3600);
+
+        let calendars =
+            Calendars::load(settings(offset), "tests_resources/calendars.toml").unwrap();
+        let result = calendars.get(Some("us"));
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().to_string(), "Unknown calendar `us`.");
+    }
+
+    #[test]
+    fn should_return_error_if_calendars_file_is_invalid() {
+        let offset = FixedOffset::west(3 * 3600);
+
+        let result = Calendars::load(settings(offset), "unknown_file.toml");
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Error reading calendars file unknown_file.toml."
+        );
+
+        let result = Calendars::load(settings(offset), "tests_resources/invalid_calendars.toml");
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "The default calendar `us` is not configured."
+        );
+
+        let result = Calendars::load(
+            settings(offset),
+            "tests_resources/invalid_weekend_calendars.toml",
+        );
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid weekend of calendar `br`."
+        );
+
+        let result = build_calendar("br", settings(offset), None, None);
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Calendar `br` needs a `holidays_file` or a `rules_file`."
+        );
+    }
+
+    fn settings(time_offset: FixedOffset) -> CalendarSettings {
+        CalendarSettings {
+            time_zone: time_offset.into(),
+            weekend: Weekend::default(),
+            observance: Observance::None,
+            load_mode: LoadMode::Strict,
+            holidays_format: None,
+        }
+    }
+}
@@ -1,19 +1,70 @@
+// Helpers fail with the `Status` returned by the RPCs, large but not worth boxing.
+#![allow(clippy::result_large_err)]
+
 use std::sync::Arc;
-use std::time::Duration;
 
-use chrono::{NaiveTime, Utc};
-use tokio::sync::mpsc;
+use chrono::{Date, DateTime, FixedOffset, NaiveTime, Utc};
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status};
 
-use crate::WorkingDays;
+use crate::calendars::Calendars;
+use crate::profiles::{Profile, Profiles};
+use crate::schedule;
+use crate::schedule::{NthWorkingDays, Window};
+use crate::scheduler::Scheduler;
+use crate::time_zone::CalendarTimeZone;
+use crate::working_days::WorkingDays;
 
 tonic::include_proto!("externalscaler");
 
+/// Longest `leadTime` or `holdTime`, in days.
+const MAX_DURATION_DAYS: i64 = 7;
+
+/// Days around today a window may start on and still be open, with the longest `leadTime` or
+/// `holdTime`.
+const SCHEDULE_MARGIN_DAYS: i64 = MAX_DURATION_DAYS + 2;
+
+/// Value reported by `GetMetrics`.
+enum MetricMode {
+    Replicas,
+    WorkingDay,
+}
+
+/// When a trigger is active, from a replica profile or from working days and windows.
+enum Schedule<'a> {
+    Profile(&'a Profile),
+    Windows {
+        nth_working_days: NthWorkingDays,
+        windows: Vec<Window>,
+        lead_time: chrono::Duration,
+        hold_time: chrono::Duration,
+    },
+}
+
 #[derive(Debug)]
 pub struct GrpcHandler {
-    pub working_days: Arc<WorkingDays>,
-    pub push_interval: u64,
+    pub calendars: Arc<Calendars>,
+    pub profiles: Arc<Profiles>,
+    pub scheduler: Arc<Scheduler>,
+}
+
+impl GrpcHandler {
+    /// Handler whose streams repeat the current state every `push_interval` seconds, `0` to
+    /// only push changes.
+    pub fn new(calendars: Arc<Calendars>, profiles: Arc<Profiles>, push_interval: u64) -> Self {
+        let scheduler =
+            Scheduler::new(Arc::clone(&calendars), Arc::clone(&profiles), push_interval);
+        GrpcHandler {
+            calendars,
+            profiles,
+            scheduler: Arc::new(scheduler),
+        }
+    }
+
+    /// Scheduler of the `StreamIsActive` streams, shared with the `Calendar` service.
+    pub fn scheduler(&self) -> Arc<Scheduler> {
+        Arc::clone(&self.scheduler)
+    }
 }
 
 #[tonic::async_trait]
@@ -22,9 +73,14 @@ impl external_scaler_server::ExternalScaler for GrpcHandler {
         &self,
         request: Request<ScaledObjectRef>,
     ) -> Result<Response<IsActiveResponse>, Status> {
-        is_active(&self.working_days, request.into_inner())
-            .await
-            .map(Response::new)
+        is_active(
+            &self.calendars,
+            &self.profiles,
+            request.into_inner(),
+            &Utc::now(),
+        )
+        .await
+        .map(Response::new)
     }
 
     type StreamIsActiveStream = ReceiverStream<Result<IsActiveResponse, Status>>;
@@ -33,20 +89,7 @@ impl external_scaler_server::ExternalScaler for GrpcHandler {
         &self,
         request: Request<ScaledObjectRef>,
     ) -> Result<Response<Self::StreamIsActiveStream>, Status> {
-        let (tx, rx) = mpsc::channel(1);
-
-        let working_days = Arc::clone(&self.working_days);
-        let push_interval = Duration::from_secs(self.push_interval);
-        let message = request.into_inner();
-        tokio::spawn(async move {
-            while !tx.is_closed() {
-                let result = is_active(&working_days, message.clone()).await;
-
-                if (tx.send(result).await).is_ok() {
-                    tokio::time::sleep(push_interval).await
-                }
-            }
-        });
+        let rx = self.scheduler.subscribe(request.into_inner());
 
         Ok(Response::new(ReceiverStream::new(rx)))
     }
@@ -70,56 +113,212 @@ impl external_scaler_server::ExternalScaler for GrpcHandler {
         &self,
         request: Request<GetMetricsRequest>,
     ) -> Result<Response<GetMetricsResponse>, Status> {
-        let nth_working_day = current_nth_working_day(&self.working_days)?;
+        let request = request.into_inner();
+        let message = request.scaled_object_ref.unwrap_or_default();
+        let metric_value = metric_value(&self.calendars, &self.profiles, &message, &Utc::now())?;
 
         Ok(Response::new(GetMetricsResponse {
             metric_values: vec![MetricValue {
-                metric_name: request.into_inner().metric_name,
-                metric_value: nth_working_day as i64,
+                metric_name: request.metric_name,
+                metric_value: metric_value.round() as i64,
+                metric_value_float: metric_value,
             }],
         }))
     }
 }
 
-async fn is_active(
-    working_days: &WorkingDays,
+/// Metric scaling to the scheduled replicas, or to `idleReplicas` outside the schedule, or the
+/// current working day when `metricMode` is `workingDay`.
+fn metric_value(
+    calendars: &Calendars,
+    profiles: &Profiles,
+    message: &ScaledObjectRef,
+    now: &DateTime<Utc>,
+) -> Result<f64, Status> {
+    let working_days = read_calendar(calendars, message, now)?;
+
+    match read_metric_mode(message)? {
+        MetricMode::Replicas => {
+            let idle_replicas = read_replicas(message, "idleReplicas", 0)?;
+            let target_size = read_target_size(message)?;
+
+            // The HPA targets an average of `targetSize` per replica
+            let replicas =
+                scheduled_replicas(&working_days, profiles, message, now)?.unwrap_or(idle_replicas);
+            Ok(replicas as f64 * target_size as f64)
+        }
+        MetricMode::WorkingDay => {
+            let time_zone = read_time_zone(message)?.unwrap_or(working_days.time_zone);
+            let (today, _) = working_days.local_now_in(time_zone, now);
+            if read_weighted_metric(message)? {
+                current_weighted_working_days(&working_days, today)
+            } else {
+                current_nth_working_day(&working_days, today).map(f64::from)
+            }
+        }
+    }
+}
+
+pub(crate) async fn is_active(
+    calendars: &Calendars,
+    profiles: &Profiles,
     message: ScaledObjectRef,
+    now: &DateTime<Utc>,
 ) -> Result<IsActiveResponse, Status> {
-    let expected_nth_working_day: u8 = read_nth_working_day_arg(&message)?;
-    let from_time = read_time(&message, "fromTime")?;
-    let to_time = read_time(&message, "toTime")?;
+    let working_days = read_calendar(calendars, &message, now)?;
+    let replicas = scheduled_replicas(&working_days, profiles, &message, now)?;
 
     read_target_size(&message)?; // Checking if present to avoid later errors
 
-    let nth_working_day = current_nth_working_day(working_days)?;
-
     Ok(IsActiveResponse {
-        result: expected_nth_working_day == nth_working_day
-            && current_time_between(working_days, from_time, to_time),
+        result: matches!(replicas, Some(replicas) if replicas > 0),
     })
 }
 
-fn read_nth_working_day_arg(message: &ScaledObjectRef) -> Result<u8, Status> {
+/// Replicas of the matching cell of the `profile`, or `activeReplicas` inside a window starting
+/// on one of the `nthWorkingDay` days. `None` outside the schedule.
+fn scheduled_replicas(
+    working_days: &WorkingDays,
+    profiles: &Profiles,
+    message: &ScaledObjectRef,
+    now: &DateTime<Utc>,
+) -> Result<Option<u32>, Status> {
+    let schedule = read_schedule(profiles, message)?;
+    let time_zone = read_time_zone(message)?.unwrap_or(working_days.time_zone);
+
+    let (today, time) = working_days.local_now_in(time_zone, now);
+    current_nth_working_day(working_days, today)?; // Checking if covered to report out of range dates
+
+    match schedule {
+        Schedule::Profile(profile) => profile
+            .replicas(working_days, today, time)
+            .map_err(|err| Status::invalid_argument(err.to_string())),
+        Schedule::Windows {
+            nth_working_days,
+            windows,
+            lead_time,
+            hold_time,
+        } => {
+            let days = windows
+                .iter()
+                .flat_map(|window| window.days(today, time, lead_time, hold_time));
+            for day in days {
+                let matches = nth_working_days
+                    .matches(working_days, day)
+                    .map_err(|err| Status::invalid_argument(err.to_string()))?;
+                if matches {
+                    return read_replicas(message, "activeReplicas", 1).map(Some);
+                }
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// Next instant the trigger may change, the closest window boundary or local midnight after
+/// `now`. `None` when every boundary falls in a daylight saving time gap.
+pub(crate) fn next_transition(
+    calendars: &Calendars,
+    profiles: &Profiles,
+    message: &ScaledObjectRef,
+    now: &DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>, Status> {
+    let working_days = read_calendar(calendars, message, now)?;
+    let schedule = read_schedule(profiles, message)?;
+    let time_zone = read_time_zone(message)?.unwrap_or(working_days.time_zone);
+    let today = time_zone.local(now).date();
+
+    let mut boundaries: Vec<_> = (1..=2)
+        .map(|days| (today + chrono::Duration::days(days)).and_hms(0, 0, 0))
+        .collect();
+    match schedule {
+        Schedule::Profile(profile) => {
+            let zero = chrono::Duration::zero();
+            for window in profile.windows() {
+                boundaries.extend(window.boundaries(today, zero, zero));
+            }
+        }
+        Schedule::Windows {
+            windows,
+            lead_time,
+            hold_time,
+            ..
+        } => {
+            for window in &windows {
+                boundaries.extend(window.boundaries(today, lead_time, hold_time));
+            }
+        }
+    }
+
+    Ok(boundaries
+        .iter()
+        .filter_map(|boundary| time_zone.instant(boundary))
+        .filter(|instant| instant > now)
+        .min())
+}
+
+/// Schedule from `profile`, or from `nthWorkingDay` and the windows.
+fn read_schedule<'a>(
+    profiles: &'a Profiles,
+    message: &ScaledObjectRef,
+) -> Result<Schedule<'a>, Status> {
+    match message.scaler_metadata.get("profile") {
+        Some(name) => profiles
+            .get(name)
+            .map(Schedule::Profile)
+            .map_err(|err| Status::invalid_argument(err.to_string())),
+        None => Ok(Schedule::Windows {
+            nth_working_days: read_nth_working_day_arg(message)?,
+            windows: read_windows(message)?,
+            lead_time: read_duration(message, "leadTime")?,
+            hold_time: read_duration(message, "holdTime")?,
+        }),
+    }
+}
+
+fn read_calendar(
+    calendars: &Calendars,
+    message: &ScaledObjectRef,
+    now: &DateTime<Utc>,
+) -> Result<Arc<WorkingDays>, Status> {
+    let value = message.scaler_metadata.get("calendar");
+    calendars
+        .get_covering(
+            value.map(String::as_str),
+            now.naive_utc().date(),
+            chrono::Duration::days(SCHEDULE_MARGIN_DAYS),
+        )
+        .map_err(|err| Status::invalid_argument(err.to_string()))
+}
+
+fn read_nth_working_day_arg(message: &ScaledObjectRef) -> Result<NthWorkingDays, Status> {
     let value = message.scaler_metadata.get("nthWorkingDay");
     match value {
         None => Err(Status::invalid_argument(
             "Missing required metadata `nthWorkingDay`.",
         )),
+        Some(value) => value
+            .parse::<NthWorkingDays>()
+            .map_err(|err| Status::invalid_argument(err.to_string())),
+    }
+}
+
+/// Windows from `windows`, or the single window from `fromTime` to `toTime`.
+fn read_windows(message: &ScaledObjectRef) -> Result<Vec<Window>, Status> {
+    let metadata = &message.scaler_metadata;
+    match metadata.get("windows") {
+        Some(_) if metadata.contains_key("fromTime") || metadata.contains_key("toTime") => {
+            Err(Status::invalid_argument(
+                "Metadata `windows` cannot be used along with `fromTime` and `toTime`.",
+            ))
+        }
         Some(value) => {
-            if let Ok(parsed) = value.parse::<u8>() {
-                if parsed <= 31 {
-                    Ok(parsed)
-                } else {
-                    Err(Status::invalid_argument(
-                        "Metadata `nthWorkingDay` should be a value between 1 and 31.",
-                    ))
-                }
-            } else {
-                Err(Status::invalid_argument(
-                    "Metadata `nthWorkingDay` should be a value between 1 and 31.",
-                ))
-            }
+            schedule::parse_windows(value).map_err(|err| Status::invalid_argument(err.to_string()))
         }
+        None => Ok(vec![Window {
+            from: read_time(message, "fromTime")?,
+            to: read_time(message, "toTime")?,
+        }]),
     }
 }
 
@@ -143,6 +342,48 @@ fn read_time(message: &ScaledObjectRef, parameter: &str) -> Result<NaiveTime, St
     }
 }
 
+/// Optional duration up to `MAX_DURATION_DAYS`, zero when missing.
+fn read_duration(message: &ScaledObjectRef, parameter: &str) -> Result<chrono::Duration, Status> {
+    match message.scaler_metadata.get(parameter) {
+        None => Ok(chrono::Duration::zero()),
+        Some(value) => schedule::parse_duration(value)
+            .filter(|duration| *duration <= chrono::Duration::days(MAX_DURATION_DAYS))
+            .ok_or_else(|| {
+                Status::invalid_argument(format!(
+                    "Metadata `{}` should be a duration up to {} days, like `15m` or `PT15M`.",
+                    parameter, MAX_DURATION_DAYS
+                ))
+            }),
+    }
+}
+
+/// Time zone overriding the one of the calendar, from `timezone` or `timeOffset`.
+fn read_time_zone(message: &ScaledObjectRef) -> Result<Option<CalendarTimeZone>, Status> {
+    let time_zone = message.scaler_metadata.get("timezone");
+    let time_offset = message.scaler_metadata.get("timeOffset");
+    match (time_zone, time_offset) {
+        (None, None) => Ok(None),
+        (Some(_), Some(_)) => Err(Status::invalid_argument(
+            "Metadata `timezone` and `timeOffset` cannot be used together.",
+        )),
+        (Some(value), None) => value.parse::<CalendarTimeZone>().map(Some).map_err(|_| {
+            Status::invalid_argument(
+                "Metadata `timezone` should be an IANA time zone name, like `America/Sao_Paulo`.",
+            )
+        }),
+        (None, Some(value)) => value
+            .parse::<i32>()
+            .ok()
+            .and_then(FixedOffset::east_opt)
+            .map(|offset| Some(offset.into()))
+            .ok_or_else(|| {
+                Status::invalid_argument(
+                    "Metadata `timeOffset` should be a value in seconds between -86399 and 86399.",
+                )
+            }),
+    }
+}
+
 fn read_target_size(message: &ScaledObjectRef) -> Result<u32, Status> {
     let value = message.scaler_metadata.get("targetSize");
     match value {
@@ -161,16 +402,55 @@ fn read_target_size(message: &ScaledObjectRef) -> Result<u32, Status> {
     }
 }
 
-fn current_nth_working_day(working_days: &WorkingDays) -> Result<u8, Status> {
-    let now = Utc::now().with_timezone(&working_days.time_offset);
-    let result = working_days.working_days_mtd(now.date());
-    result.map_err(|err| Status::invalid_argument(err.to_string()))
+fn read_replicas(message: &ScaledObjectRef, parameter: &str, default: u32) -> Result<u32, Status> {
+    match message.scaler_metadata.get(parameter) {
+        None => Ok(default),
+        Some(value) => value.parse::<u32>().map_err(|_| {
+            Status::invalid_argument(format!(
+                "Metadata `{}` should be an integer value.",
+                parameter
+            ))
+        }),
+    }
 }
 
-fn current_time_between(working_days: &WorkingDays, from: NaiveTime, to: NaiveTime) -> bool {
-    let time = Utc::now().with_timezone(&working_days.time_offset).time();
+fn read_metric_mode(message: &ScaledObjectRef) -> Result<MetricMode, Status> {
+    match message
+        .scaler_metadata
+        .get("metricMode")
+        .map(String::as_str)
+    {
+        None | Some("replicas") => Ok(MetricMode::Replicas),
+        Some("workingDay") => Ok(MetricMode::WorkingDay),
+        Some(_) => Err(Status::invalid_argument(
+            "Metadata `metricMode` should be `replicas` or `workingDay`.",
+        )),
+    }
+}
 
-    from <= time && time <= to
+fn read_weighted_metric(message: &ScaledObjectRef) -> Result<bool, Status> {
+    match message.scaler_metadata.get("weightedMetric") {
+        None => Ok(false),
+        Some(value) => value.parse::<bool>().map_err(|_| {
+            Status::invalid_argument("Metadata `weightedMetric` should be `true` or `false`.")
+        }),
+    }
+}
+
+fn current_nth_working_day(
+    working_days: &WorkingDays,
+    today: Date<FixedOffset>,
+) -> Result<u8, Status> {
+    let result = working_days.working_days_mtd(today);
+    result.map_err(|err| Status::invalid_argument(err.to_string()))
+}
+
+fn current_weighted_working_days(
+    working_days: &WorkingDays,
+    today: Date<FixedOffset>,
+) -> Result<f64, Status> {
+    let result = working_days.weighted_working_days_mtd(today);
+    result.map_err(|err| Status::invalid_argument(err.to_string()))
 }
 
 #[cfg(test)]
@@ -178,19 +458,25 @@ mod tests {
     use std::collections::HashMap;
     use std::sync::Arc;
 
-    use chrono::{FixedOffset, TimeZone};
+    use chrono::{FixedOffset, TimeZone, Utc};
+    use chrono_tz::America::New_York;
     use tonic::Request;
 
+    use crate::calendars::Calendars;
     use crate::handler::external_scaler_server::ExternalScaler;
-    use crate::handler::{GetMetricsRequest, ScaledObjectRef};
-    use crate::{GrpcHandler, WorkingDays};
+    use crate::handler::{
+        is_active, metric_value, next_transition, GetMetricsRequest, GrpcHandler, ScaledObjectRef,
+    };
+    use crate::holiday::Observance;
+    use crate::holidays_loader;
+    use crate::holidays_loader::LoadMode;
+    use crate::profiles::Profiles;
+    use crate::time_zone::CalendarTimeZone;
+    use crate::working_days::{Weekend, WorkingDays};
 
     #[tokio::test]
     async fn should_require_valid_nth_working_day_argument() {
-        let handler = GrpcHandler {
-            working_days: simple_working_days(),
-            push_interval: 60,
-        };
+        let handler = GrpcHandler::new(simple_calendars(), Arc::new(Profiles::default()), 60);
 
         let result = handler
             .is_active(Request::new(ScaledObjectRef {
@@ -220,7 +506,7 @@ mod tests {
         assert!(result.is_err());
         assert_eq!(
             result.unwrap_err().message().to_string(),
-            "Metadata `nthWorkingDay` should be a value between 1 and 31."
+            "Invalid working day `jose` in metadata `nthWorkingDay`. Expected values between -31 and 31, like `1-3,5,-1`."
         );
 
         let mut metadata: HashMap<String, String> = HashMap::new();
@@ -237,16 +523,30 @@ mod tests {
         assert!(result.is_err());
         assert_eq!(
             result.unwrap_err().message().to_string(),
-            "Metadata `nthWorkingDay` should be a value between 1 and 31."
+            "Invalid working day `32` in metadata `nthWorkingDay`. Expected values between -31 and 31, like `1-3,5,-1`."
+        );
+
+        let mut metadata: HashMap<String, String> = HashMap::new();
+        metadata.insert("nthWorkingDay".to_string(), "-32".to_string());
+
+        let result = handler
+            .is_active(Request::new(ScaledObjectRef {
+                name: "name".to_string(),
+                namespace: "namespace".to_string(),
+                scaler_metadata: metadata,
+            }))
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().message().to_string(),
+            "Invalid working day `-32` in metadata `nthWorkingDay`. Expected values between -31 and 31, like `1-3,5,-1`."
         );
     }
 
     #[tokio::test]
     async fn should_require_valid_target_size_argument() {
-        let handler = GrpcHandler {
-            working_days: simple_working_days(),
-            push_interval: 60,
-        };
+        let handler = GrpcHandler::new(simple_calendars(), Arc::new(Profiles::default()), 60);
 
         let mut metadata: HashMap<String, String> = HashMap::new();
         metadata.insert("nthWorkingDay".to_string(), "5".to_string());
@@ -267,10 +567,7 @@ mod tests {
             "Missing required metadata `targetSize`."
         );
 
-        let handler = GrpcHandler {
-            working_days: simple_working_days(),
-            push_interval: 60,
-        };
+        let handler = GrpcHandler::new(simple_calendars(), Arc::new(Profiles::default()), 60);
 
         let result = handler
             .get_metric_spec(Request::new(ScaledObjectRef {
@@ -286,10 +583,7 @@ mod tests {
             "Missing required metadata `targetSize`."
         );
 
-        let handler = GrpcHandler {
-            working_days: simple_working_days(),
-            push_interval: 60,
-        };
+        let handler = GrpcHandler::new(simple_calendars(), Arc::new(Profiles::default()), 60);
 
         let mut metadata: HashMap<String, String> = HashMap::new();
         metadata.insert("targetSize".to_string(), "jose".to_string());
@@ -311,10 +605,7 @@ mod tests {
 
     #[tokio::test]
     async fn should_require_valid_from_date_and_to_date() {
-        let handler = GrpcHandler {
-            working_days: simple_working_days(),
-            push_interval: 60,
-        };
+        let handler = GrpcHandler::new(simple_calendars(), Arc::new(Profiles::default()), 60);
 
         let mut metadata: HashMap<String, String> = HashMap::new();
         metadata.insert("nthWorkingDay".to_string(), "5".to_string());
@@ -373,10 +664,7 @@ mod tests {
 
     #[tokio::test]
     async fn should_return_error_if_today_is_out_of_range() {
-        let handler = GrpcHandler {
-            working_days: out_of_range_working_days(),
-            push_interval: 60,
-        };
+        let handler = GrpcHandler::new(out_of_range_calendars(), Arc::new(Profiles::default()), 60);
 
         let mut metadata: HashMap<String, String> = HashMap::new();
         metadata.insert("nthWorkingDay".to_string(), "5".to_string());
@@ -388,7 +676,7 @@ mod tests {
             .is_active(Request::new(ScaledObjectRef {
                 name: "name".to_string(),
                 namespace: "namespace".to_string(),
-                scaler_metadata: metadata,
+                scaler_metadata: metadata.clone(),
             }))
             .await;
 
@@ -397,7 +685,11 @@ mod tests {
 
         let result = handler
             .get_metrics(Request::new(GetMetricsRequest {
-                scaled_object_ref: None,
+                scaled_object_ref: Some(ScaledObjectRef {
+                    name: "name".to_string(),
+                    namespace: "namespace".to_string(),
+                    scaler_metadata: metadata,
+                }),
                 metric_name: "metric_name".to_string(),
             }))
             .await;
@@ -408,10 +700,7 @@ mod tests {
 
     #[tokio::test]
     async fn should_execute_without_errors() {
-        let handler = GrpcHandler {
-            working_days: simple_working_days(),
-            push_interval: 60,
-        };
+        let handler = GrpcHandler::new(simple_calendars(), Arc::new(Profiles::default()), 60);
 
         let mut metadata: HashMap<String, String> = HashMap::new();
         metadata.insert("nthWorkingDay".to_string(), "5".to_string());
@@ -433,28 +722,166 @@ mod tests {
             .get_metric_spec(Request::new(ScaledObjectRef {
                 name: "name".to_string(),
                 namespace: "namespace".to_string(),
-                scaler_metadata: metadata,
+                scaler_metadata: metadata.clone(),
+            }))
+            .await;
+
+        assert!(result.is_ok());
+
+        let result = handler
+            .get_metrics(Request::new(GetMetricsRequest {
+                scaled_object_ref: Some(ScaledObjectRef {
+                    name: "name".to_string(),
+                    namespace: "namespace".to_string(),
+                    scaler_metadata: metadata,
+                }),
+                metric_name: "metric_name".to_string(),
+            }))
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_select_calendar_from_metadata() {
+        let handler = GrpcHandler::new(named_calendars(), Arc::new(Profiles::default()), 60);
+
+        let mut metadata: HashMap<String, String> = HashMap::new();
+        metadata.insert("nthWorkingDay".to_string(), "5".to_string());
+        metadata.insert("fromTime".to_string(), "06:00:00".to_string());
+        metadata.insert("toTime".to_string(), "18:00:00".to_string());
+        metadata.insert("targetSize".to_string(), "10".to_string());
+
+        let result = handler
+            .is_active(Request::new(ScaledObjectRef {
+                name: "name".to_string(),
+                namespace: "namespace".to_string(),
+                scaler_metadata: metadata.clone(),
+            }))
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().message().to_string(),
+            "No calendar selected and no default calendar configured."
+        );
+
+        metadata.insert("calendar".to_string(), "us".to_string());
+
+        let result = handler
+            .is_active(Request::new(ScaledObjectRef {
+                name: "name".to_string(),
+                namespace: "namespace".to_string(),
+                scaler_metadata: metadata.clone(),
+            }))
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().message().to_string(),
+            "Unknown calendar `us`."
+        );
+
+        metadata.insert("calendar".to_string(), "old".to_string());
+
+        let result = handler
+            .is_active(Request::new(ScaledObjectRef {
+                name: "name".to_string(),
+                namespace: "namespace".to_string(),
+                scaler_metadata: metadata.clone(),
+            }))
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().message().to_string(), "The requested date was not calculated. Table processed for dates between 2020-01-01-03:00 and 2021-12-31-03:00.");
+
+        metadata.insert("calendar".to_string(), "br".to_string());
+
+        let result = handler
+            .is_active(Request::new(ScaledObjectRef {
+                name: "name".to_string(),
+                namespace: "namespace".to_string(),
+                scaler_metadata: metadata.clone(),
+            }))
+            .await;
+
+        assert!(result.is_ok());
+
+        let mut last_working_day = metadata.clone();
+        last_working_day.insert("nthWorkingDay".to_string(), "-1".to_string());
+
+        let result = handler
+            .is_active(Request::new(ScaledObjectRef {
+                name: "name".to_string(),
+                namespace: "namespace".to_string(),
+                scaler_metadata: last_working_day,
+            }))
+            .await;
+
+        assert!(result.is_ok());
+
+        let result = handler
+            .get_metrics(Request::new(GetMetricsRequest {
+                scaled_object_ref: Some(ScaledObjectRef {
+                    name: "name".to_string(),
+                    namespace: "namespace".to_string(),
+                    scaler_metadata: metadata,
+                }),
+                metric_name: "metric_name".to_string(),
             }))
             .await;
 
         assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_report_weighted_metric() {
+        let handler = GrpcHandler::new(simple_calendars(), Arc::new(Profiles::default()), 60);
+
+        let mut metadata: HashMap<String, String> = HashMap::new();
+        metadata.insert("metricMode".to_string(), "workingDay".to_string());
+        metadata.insert("weightedMetric".to_string(), "yes".to_string());
+
+        let result = handler
+            .get_metrics(Request::new(GetMetricsRequest {
+                scaled_object_ref: Some(ScaledObjectRef {
+                    name: "name".to_string(),
+                    namespace: "namespace".to_string(),
+                    scaler_metadata: metadata.clone(),
+                }),
+                metric_name: "metric_name".to_string(),
+            }))
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().message().to_string(),
+            "Metadata `weightedMetric` should be `true` or `false`."
+        );
+
+        metadata.insert("weightedMetric".to_string(), "true".to_string());
 
         let result = handler
             .get_metrics(Request::new(GetMetricsRequest {
-                scaled_object_ref: None,
+                scaled_object_ref: Some(ScaledObjectRef {
+                    name: "name".to_string(),
+                    namespace: "namespace".to_string(),
+                    scaler_metadata: metadata,
+                }),
                 metric_name: "metric_name".to_string(),
             }))
             .await;
 
         assert!(result.is_ok());
+
+        // No partial holidays, so the weighted value is the nth working day
+        let metric = result.unwrap().into_inner().metric_values.remove(0);
+        assert_eq!(metric.metric_value_float, metric.metric_value as f64);
     }
 
     #[tokio::test]
     async fn should_execute_stream_without_errors() {
-        let handler = GrpcHandler {
-            working_days: simple_working_days(),
-            push_interval: 1,
-        };
+        let handler = GrpcHandler::new(simple_calendars(), Arc::new(Profiles::default()), 1);
 
         let mut metadata: HashMap<String, String> = HashMap::new();
         metadata.insert("nthWorkingDay".to_string(), "5".to_string());
@@ -480,23 +907,503 @@ mod tests {
         drop(stream);
     }
 
-    fn simple_working_days() -> Arc<WorkingDays> {
+    #[tokio::test]
+    async fn should_push_stream_only_on_change() {
+        let handler = GrpcHandler::new(simple_calendars(), Arc::new(Profiles::default()), 0);
+
+        let mut metadata: HashMap<String, String> = HashMap::new();
+        metadata.insert("nthWorkingDay".to_string(), "5".to_string());
+        metadata.insert("fromTime".to_string(), "06:00:00".to_string());
+        metadata.insert("toTime".to_string(), "18:00:00".to_string());
+        metadata.insert("targetSize".to_string(), "10".to_string());
+
+        let result = handler
+            .stream_is_active(Request::new(ScaledObjectRef {
+                name: "name".to_string(),
+                namespace: "namespace".to_string(),
+                scaler_metadata: metadata,
+            }))
+            .await;
+
+        let mut stream = result.unwrap().into_inner().into_inner();
+
+        assert!(stream.recv().await.unwrap().is_ok());
+        let next = tokio::time::timeout(std::time::Duration::from_secs(2), stream.recv()).await;
+        assert!(next.is_err());
+    }
+
+    #[tokio::test]
+    async fn should_follow_daylight_saving_time() {
+        let calendars = new_york_calendars();
+        let profiles = Arc::new(Profiles::default());
+        let active = |nth_working_day: &str, from_time: &str, to_time: &str, now| {
+            let mut metadata: HashMap<String, String> = HashMap::new();
+            metadata.insert("nthWorkingDay".to_string(), nth_working_day.to_string());
+            metadata.insert("fromTime".to_string(), from_time.to_string());
+            metadata.insert("toTime".to_string(), to_time.to_string());
+            metadata.insert("targetSize".to_string(), "10".to_string());
+
+            let message = ScaledObjectRef {
+                name: "name".to_string(),
+                namespace: "namespace".to_string(),
+                scaler_metadata: metadata,
+            };
+            let calendars = Arc::clone(&calendars);
+            let profiles = Arc::clone(&profiles);
+            async move {
+                is_active(&calendars, &profiles, message, &now)
+                    .await
+                    .unwrap()
+                    .result
+            }
+        };
+
+        // Spring forward, 2023-03-12 02:00 EST becomes 03:00 EDT
+        let spring_forward = Utc.ymd(2023, 3, 12).and_hms(7, 30, 0);
+        assert!(active("12", "03:00:00", "03:59:59", spring_forward).await);
+        assert!(!active("12", "02:00:00", "02:59:59", spring_forward).await);
+
+        // Fall back, 2023-11-05 02:00 EDT becomes 01:00 EST and 01:30 happens twice
+        let window = ("5", "01:00:00", "01:59:59");
+        let first = Utc.ymd(2023, 11, 5).and_hms(5, 30, 0);
+        let second = Utc.ymd(2023, 11, 5).and_hms(6, 30, 0);
+        let after = Utc.ymd(2023, 11, 5).and_hms(7, 30, 0);
+        assert!(active(window.0, window.1, window.2, first).await);
+        assert!(active(window.0, window.1, window.2, second).await);
+        assert!(!active(window.0, window.1, window.2, after).await);
+
+        // 00:30 EDT is still the previous day at a fixed -05:00 offset
+        let midnight = Utc.ymd(2023, 7, 3).and_hms(4, 30, 0);
+        assert!(active("3", "00:00:00", "00:59:59", midnight).await);
+    }
+
+    #[tokio::test]
+    async fn should_override_time_zone_from_metadata() {
+        let calendars = new_york_calendars();
+        let profiles = Profiles::default();
+        let mut metadata: HashMap<String, String> = HashMap::new();
+        metadata.insert("nthWorkingDay".to_string(), "3".to_string());
+        metadata.insert("fromTime".to_string(), "06:00:00".to_string());
+        metadata.insert("toTime".to_string(), "06:59:59".to_string());
+        metadata.insert("targetSize".to_string(), "10".to_string());
+        let message = |metadata: &HashMap<String, String>| ScaledObjectRef {
+            name: "name".to_string(),
+            namespace: "namespace".to_string(),
+            scaler_metadata: metadata.clone(),
+        };
+
+        // 2023-07-03 06:30 in Lisbon, 01:30 in São Paulo and 02:30 in New York
+        let now = Utc.ymd(2023, 7, 3).and_hms(5, 30, 0);
+        let result = is_active(&calendars, &profiles, message(&metadata), &now).await;
+        assert!(!result.unwrap().result);
+
+        metadata.insert("timezone".to_string(), "Europe/Lisbon".to_string());
+        let result = is_active(&calendars, &profiles, message(&metadata), &now).await;
+        assert!(result.unwrap().result);
+
+        metadata.insert("timeOffset".to_string(), "3600".to_string());
+        let result = is_active(&calendars, &profiles, message(&metadata), &now).await;
+        assert_eq!(
+            result.unwrap_err().message(),
+            "Metadata `timezone` and `timeOffset` cannot be used together."
+        );
+
+        metadata.remove("timezone");
+        let result = is_active(&calendars, &profiles, message(&metadata), &now).await;
+        assert!(result.unwrap().result);
+
+        // 2023-07-02 23:30 in São Paulo, still the 2nd working day
+        metadata.insert("timeOffset".to_string(), "-10800".to_string());
+        metadata.insert("fromTime".to_string(), "23:00:00".to_string());
+        metadata.insert("toTime".to_string(), "23:59:59".to_string());
+        let now = Utc.ymd(2023, 7, 3).and_hms(2, 30, 0);
+        let result = is_active(&calendars, &profiles, message(&metadata), &now).await;
+        assert!(!result.unwrap().result);
+        metadata.insert("nthWorkingDay".to_string(), "2".to_string());
+        let result = is_active(&calendars, &profiles, message(&metadata), &now).await;
+        assert!(result.unwrap().result);
+
+        metadata.insert("timeOffset".to_string(), "86400".to_string());
+        let result = is_active(&calendars, &profiles, message(&metadata), &now).await;
+        assert_eq!(
+            result.unwrap_err().message(),
+            "Metadata `timeOffset` should be a value in seconds between -86399 and 86399."
+        );
+
+        metadata.remove("timeOffset");
+        metadata.insert("timezone".to_string(), "Lisbon".to_string());
+        let result = is_active(&calendars, &profiles, message(&metadata), &now).await;
+        assert_eq!(
+            result.unwrap_err().message(),
+            "Metadata `timezone` should be an IANA time zone name, like `America/Sao_Paulo`."
+        );
+    }
+
+    #[tokio::test]
+    async fn should_be_active_on_any_window_and_working_day() {
+        let calendars = new_york_calendars();
+        let profiles = Profiles::default();
+        let mut metadata: HashMap<String, String> = HashMap::new();
+        metadata.insert("nthWorkingDay".to_string(), "1-2,5,-1".to_string());
+        metadata.insert("windows".to_string(), "06:00-09:00,18:00-20:00".to_string());
+        metadata.insert("targetSize".to_string(), "10".to_string());
+        let message = |metadata: &HashMap<String, String>| ScaledObjectRef {
+            name: "name".to_string(),
+            namespace: "namespace".to_string(),
+            scaler_metadata: metadata.clone(),
+        };
+
+        // New York times, every day is a working day
+        for (now, active) in [
+            (Utc.ymd(2023, 7, 2).and_hms(11, 0, 0), true), // 2nd, 07:00
+            (Utc.ymd(2023, 7, 2).and_hms(23, 0, 0), true), // 2nd, 19:00
+            (Utc.ymd(2023, 7, 2).and_hms(17, 0, 0), false), // 2nd, 13:00
+            (Utc.ymd(2023, 7, 3).and_hms(11, 0, 0), false), // 3rd, 07:00
+            (Utc.ymd(2023, 7, 5).and_hms(11, 0, 0), true), // 5th, 07:00
+            (Utc.ymd(2023, 7, 31).and_hms(23, 0, 0), true), // 31st, 19:00
+        ] {
+            let result = is_active(&calendars, &profiles, message(&metadata), &now).await;
+            assert_eq!(result.unwrap().result, active);
+        }
+
+        let now = Utc::now();
+        metadata.insert("fromTime".to_string(), "06:00:00".to_string());
+        let result = is_active(&calendars, &profiles, message(&metadata), &now).await;
+        assert_eq!(
+            result.unwrap_err().message(),
+            "Metadata `windows` cannot be used along with `fromTime` and `toTime`."
+        );
+
+        metadata.remove("fromTime");
+        metadata.insert("windows".to_string(), "06:00-09:00;18:00-20:00".to_string());
+        let result = is_active(&calendars, &profiles, message(&metadata), &now).await;
+        assert_eq!(
+            result.unwrap_err().message(),
+            "Invalid window `06:00-09:00;18:00-20:00` in metadata `windows`. Expected times formatted as `%H:%M` or `%H:%M:%S`, like `06:00-09:00`."
+        );
+
+        metadata.insert("windows".to_string(), "06:00-09:00".to_string());
+        metadata.insert("nthWorkingDay".to_string(), "5-1".to_string());
+        let result = is_active(&calendars, &profiles, message(&metadata), &now).await;
+        assert_eq!(
+            result.unwrap_err().message(),
+            "Invalid range `5-1` in metadata `nthWorkingDay`. Ranges go from the lowest to the highest day, both positive or both negative."
+        );
+    }
+
+    #[tokio::test]
+    async fn should_match_the_working_day_that_started_the_window() {
+        let calendars = new_york_calendars();
+        let profiles = Profiles::default();
+        let mut metadata: HashMap<String, String> = HashMap::new();
+        metadata.insert("nthWorkingDay".to_string(), "3".to_string());
+        metadata.insert("fromTime".to_string(), "22:00:00".to_string());
+        metadata.insert("toTime".to_string(), "02:00:00".to_string());
+        metadata.insert("targetSize".to_string(), "10".to_string());
+        let message = ScaledObjectRef {
+            name: "name".to_string(),
+            namespace: "namespace".to_string(),
+            scaler_metadata: metadata,
+        };
+
+        // 2023-07-03 23:00 and 2023-07-04 01:00 in New York
+        for (now, active) in [
+            (Utc.ymd(2023, 7, 4).and_hms(3, 0, 0), true),
+            (Utc.ymd(2023, 7, 4).and_hms(5, 0, 0), true),
+            (Utc.ymd(2023, 7, 4).and_hms(7, 0, 0), false),
+            (Utc.ymd(2023, 7, 5).and_hms(3, 0, 0), false),
+        ] {
+            let result = is_active(&calendars, &profiles, message.clone(), &now).await;
+            assert_eq!(result.unwrap().result, active);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_report_replicas_from_schedule() {
+        let calendars = new_york_calendars();
+        let profiles = Profiles::default();
+        let mut metadata: HashMap<String, String> = HashMap::new();
+        metadata.insert("nthWorkingDay".to_string(), "5".to_string());
+        metadata.insert("fromTime".to_string(), "06:00:00".to_string());
+        metadata.insert("toTime".to_string(), "18:00:00".to_string());
+        metadata.insert("targetSize".to_string(), "10".to_string());
+        metadata.insert("activeReplicas".to_string(), "4".to_string());
+        let message = |metadata: &HashMap<String, String>| ScaledObjectRef {
+            name: "name".to_string(),
+            namespace: "namespace".to_string(),
+            scaler_metadata: metadata.clone(),
+        };
+
+        // 2023-07-05 07:00 and 20:00 in New York, the 5th working day
+        let inside = Utc.ymd(2023, 7, 5).and_hms(11, 0, 0);
+        let outside = Utc.ymd(2023, 7, 6).and_hms(0, 0, 0);
+
+        assert_eq!(
+            metric_value(&calendars, &profiles, &message(&metadata), &inside).unwrap(),
+            40.0
+        );
+        assert_eq!(
+            metric_value(&calendars, &profiles, &message(&metadata), &outside).unwrap(),
+            0.0
+        );
+
+        metadata.insert("idleReplicas".to_string(), "1".to_string());
+        assert_eq!(
+            metric_value(&calendars, &profiles, &message(&metadata), &outside).unwrap(),
+            10.0
+        );
+
+        metadata.insert("metricMode".to_string(), "workingDay".to_string());
+        assert_eq!(
+            metric_value(&calendars, &profiles, &message(&metadata), &inside).unwrap(),
+            5.0
+        );
+
+        metadata.insert("metricMode".to_string(), "days".to_string());
+        let result = metric_value(&calendars, &profiles, &message(&metadata), &inside);
+        assert_eq!(
+            result.unwrap_err().message(),
+            "Metadata `metricMode` should be `replicas` or `workingDay`."
+        );
+
+        metadata.remove("metricMode");
+        metadata.insert("idleReplicas".to_string(), "-1".to_string());
+        let result = metric_value(&calendars, &profiles, &message(&metadata), &inside);
+        assert_eq!(
+            result.unwrap_err().message(),
+            "Metadata `idleReplicas` should be an integer value."
+        );
+    }
+
+    #[tokio::test]
+    async fn should_report_replicas_from_profile() {
+        let calendars = new_york_calendars();
+        let profiles = Profiles::load("tests_resources/profiles.toml").unwrap();
+        let mut metadata: HashMap<String, String> = HashMap::new();
+        metadata.insert("profile".to_string(), "month_end".to_string());
+        metadata.insert("targetSize".to_string(), "10".to_string());
+        let message = |metadata: &HashMap<String, String>| ScaledObjectRef {
+            name: "name".to_string(),
+            namespace: "namespace".to_string(),
+            scaler_metadata: metadata.clone(),
+        };
+
+        // New York times, every day is a working day
+        for (now, replicas) in [
+            (Utc.ymd(2023, 7, 1).and_hms(16, 0, 0), 40.0), // 1st, 12:00
+            (Utc.ymd(2023, 7, 2).and_hms(16, 0, 0), 20.0), // 2nd, 12:00
+            (Utc.ymd(2023, 7, 3).and_hms(0, 0, 0), 5.0),   // 2nd, 20:00
+            (Utc.ymd(2023, 7, 10).and_hms(16, 0, 0), 0.0), // 10th, 12:00
+            (Utc.ymd(2023, 7, 31).and_hms(16, 0, 0), 30.0), // 31st, 12:00
+        ] {
+            let result = metric_value(&calendars, &profiles, &message(&metadata), &now);
+            assert_eq!(result.unwrap(), replicas * 10.0);
+
+            let result = is_active(&calendars, &profiles, message(&metadata), &now).await;
+            assert_eq!(result.unwrap().result, replicas > 0.0);
+        }
+
+        metadata.insert("profile".to_string(), "payroll".to_string());
+        let result = is_active(&calendars, &profiles, message(&metadata), &Utc::now()).await;
+        assert_eq!(result.unwrap_err().message(), "Unknown profile `payroll`.");
+    }
+
+    #[tokio::test]
+    async fn should_activate_lead_time_before_and_hold_time_after_window() {
+        let calendars = new_york_calendars();
+        let profiles = Profiles::default();
+        let mut metadata: HashMap<String, String> = HashMap::new();
+        metadata.insert("nthWorkingDay".to_string(), "3".to_string());
+        metadata.insert("fromTime".to_string(), "00:05:00".to_string());
+        metadata.insert("toTime".to_string(), "06:00:00".to_string());
+        metadata.insert("targetSize".to_string(), "10".to_string());
+        metadata.insert("leadTime".to_string(), "PT15M".to_string());
+        metadata.insert("holdTime".to_string(), "30m".to_string());
+        let message = |metadata: &HashMap<String, String>| ScaledObjectRef {
+            name: "name".to_string(),
+            namespace: "namespace".to_string(),
+            scaler_metadata: metadata.clone(),
+        };
+
+        // New York times, the lead time starts on the 2nd, before the 3rd working day
+        for (now, active) in [
+            (Utc.ymd(2023, 7, 3).and_hms(3, 45, 0), false), // 2nd, 23:45
+            (Utc.ymd(2023, 7, 3).and_hms(3, 50, 0), true),  // 2nd, 23:50
+            (Utc.ymd(2023, 7, 3).and_hms(10, 30, 0), true), // 3rd, 06:30
+            (Utc.ymd(2023, 7, 3).and_hms(10, 31, 0), false), // 3rd, 06:31
+            (Utc.ymd(2023, 7, 4).and_hms(3, 50, 0), false), // 3rd, 23:50
+        ] {
+            let result = is_active(&calendars, &profiles, message(&metadata), &now).await;
+            assert_eq!(result.unwrap().result, active);
+        }
+
+        metadata.insert("leadTime".to_string(), "8".to_string());
+        let result = is_active(&calendars, &profiles, message(&metadata), &Utc::now()).await;
+        assert_eq!(
+            result.unwrap_err().message(),
+            "Metadata `leadTime` should be a duration up to 7 days, like `15m` or `PT15M`."
+        );
+    }
+
+    #[tokio::test]
+    async fn should_reject_lead_and_hold_times_above_seven_days() {
+        let calendars = new_york_calendars();
+        let profiles = Profiles::default();
+        let mut metadata: HashMap<String, String> = HashMap::new();
+        metadata.insert("nthWorkingDay".to_string(), "3".to_string());
+        metadata.insert("fromTime".to_string(), "00:05:00".to_string());
+        metadata.insert("toTime".to_string(), "06:00:00".to_string());
+        metadata.insert("targetSize".to_string(), "10".to_string());
+        metadata.insert("leadTime".to_string(), "7d".to_string());
+        let message = |metadata: &HashMap<String, String>| ScaledObjectRef {
+            name: "name".to_string(),
+            namespace: "namespace".to_string(),
+            scaler_metadata: metadata.clone(),
+        };
+        let now = Utc.ymd(2023, 7, 3).and_hms(3, 50, 0);
+
+        let result = is_active(&calendars, &profiles, message(&metadata), &now).await;
+        assert!(result.unwrap().result);
+
+        for (parameter, value) in [
+            ("leadTime", "7d 1s"),
+            ("leadTime", "1000000y"),
+            ("holdTime", "P1000Y"),
+        ] {
+            let mut metadata = metadata.clone();
+            metadata.insert(parameter.to_string(), value.to_string());
+            let result = is_active(&calendars, &profiles, message(&metadata), &now).await;
+            let status = result.unwrap_err();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+            assert_eq!(
+                status.message(),
+                format!(
+                    "Metadata `{}` should be a duration up to 7 days, like `15m` or `PT15M`.",
+                    parameter
+                )
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn should_extend_calendars_to_days_reached_by_lead_time() {
+        let offset = FixedOffset::west(3 * 3600);
+        let rules = holidays_loader::load_rules(
+            "tests_resources/holiday_rules.csv",
+            Observance::None,
+            LoadMode::Strict,
+        )
+        .unwrap();
+        let working_days =
+            WorkingDays::build_with_rules(offset, Weekend::default(), vec![], rules, 2023, 2023);
+        let calendars = Arc::new(Calendars::single(working_days));
+        let profiles = Profiles::default();
+        let mut metadata: HashMap<String, String> = HashMap::new();
+        metadata.insert("nthWorkingDay".to_string(), "1".to_string());
+        metadata.insert("fromTime".to_string(), "09:00:00".to_string());
+        metadata.insert("toTime".to_string(), "18:00:00".to_string());
+        metadata.insert("targetSize".to_string(), "10".to_string());
+        metadata.insert("leadTime".to_string(), "P7D".to_string());
+        let message = ScaledObjectRef {
+            name: "name".to_string(),
+            namespace: "namespace".to_string(),
+            scaler_metadata: metadata,
+        };
+
+        // The first working day of 2024 is January 2nd, after the New Year holiday
+        let now = Utc.ymd(2023, 12, 28).and_hms(15, 0, 0);
+        let result = is_active(&calendars, &profiles, message, &now).await;
+        assert!(result.unwrap().result);
+        assert_eq!(
+            calendars.get(None).unwrap().end_date,
+            offset.ymd(2024, 12, 31)
+        );
+    }
+
+    #[test]
+    fn should_find_next_transition() {
+        let calendars = new_york_calendars();
+        let profiles = Profiles::load("tests_resources/profiles.toml").unwrap();
+        let mut metadata: HashMap<String, String> = HashMap::new();
+        metadata.insert("nthWorkingDay".to_string(), "3".to_string());
+        metadata.insert("fromTime".to_string(), "06:00:00".to_string());
+        metadata.insert("toTime".to_string(), "18:00:00".to_string());
+        metadata.insert("leadTime".to_string(), "10m".to_string());
+        let message = |metadata: &HashMap<String, String>| ScaledObjectRef {
+            name: "name".to_string(),
+            namespace: "namespace".to_string(),
+            scaler_metadata: metadata.clone(),
+        };
+        let next = |metadata: &HashMap<String, String>, now| {
+            next_transition(&calendars, &profiles, &message(metadata), &now).unwrap()
+        };
+
+        // New York times, 2023-07-03 05:50 and 18:00:01 EDT
+        let opening = Utc.ymd(2023, 7, 3).and_hms(9, 50, 0);
+        let closing = Utc.ymd(2023, 7, 3).and_hms(22, 0, 1);
+        assert_eq!(
+            next(&metadata, Utc.ymd(2023, 7, 3).and_hms(5, 0, 0)),
+            Some(opening)
+        );
+        assert_eq!(next(&metadata, opening), Some(closing));
+        assert_eq!(
+            next(&metadata, closing),
+            Some(Utc.ymd(2023, 7, 4).and_hms(4, 0, 0))
+        );
+
+        // Whole day cells of profiles change at local midnight, 2023-07-01 20:00 EDT
+        metadata.insert("profile".to_string(), "month_end".to_string());
+        assert_eq!(
+            next(&metadata, Utc.ymd(2023, 7, 2).and_hms(0, 0, 0)),
+            Some(Utc.ymd(2023, 7, 2).and_hms(4, 0, 0))
+        );
+    }
+
+    fn new_york_calendars() -> Arc<Calendars> {
+        let time_zone = CalendarTimeZone::Named(New_York);
+        let offset = time_zone.date_offset();
+        let holidays = vec![offset.ymd(2023, 1, 1).into()];
+
+        let working_days = WorkingDays::build(offset, Weekend::new(&[]), holidays)
+            .unwrap()
+            .with_time_zone(time_zone);
+
+        Arc::new(Calendars::single(working_days))
+    }
+
+    fn simple_calendars() -> Arc<Calendars> {
+        Arc::new(Calendars::single(simple_working_days()))
+    }
+
+    fn out_of_range_calendars() -> Arc<Calendars> {
+        Arc::new(Calendars::single(out_of_range_working_days()))
+    }
+
+    fn named_calendars() -> Arc<Calendars> {
+        let mut tables = HashMap::new();
+        tables.insert("br".to_string(), Arc::new(simple_working_days()));
+        tables.insert("old".to_string(), Arc::new(out_of_range_working_days()));
+
+        Arc::new(Calendars::new(None, tables))
+    }
+
+    fn simple_working_days() -> WorkingDays {
         let mut holidays = Vec::new();
         let offset = FixedOffset::west(3 * 3600);
 
-        holidays.push(offset.ymd(2022, 6, 5));
-        holidays.push(offset.ymd(2122, 6, 5));
+        holidays.push(offset.ymd(2022, 6, 5).into());
+        holidays.push(offset.ymd(2122, 6, 5).into());
 
-        Arc::new(WorkingDays::build(offset, holidays).unwrap())
+        WorkingDays::build(offset, Weekend::default(), holidays).unwrap()
     }
 
-    fn out_of_range_working_days() -> Arc<WorkingDays> {
+    fn out_of_range_working_days() -> WorkingDays {
         let mut holidays = Vec::new();
         let offset = FixedOffset::west(3 * 3600);
 
-        holidays.push(offset.ymd(2020, 6, 5));
-        holidays.push(offset.ymd(2021, 6, 5));
+        holidays.push(offset.ymd(2020, 6, 5).into());
+        holidays.push(offset.ymd(2021, 6, 5).into());
 
-        Arc::new(WorkingDays::build(offset, holidays).unwrap())
+        WorkingDays::build(offset, Weekend::default(), holidays).unwrap()
     }
 }
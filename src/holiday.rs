@@ -0,0 +1,447 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use chrono::{Date, Datelike, Duration, FixedOffset};
+use thiserror::Error;
+
+use crate::working_days::Weekend;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Holiday {
+    pub date: Date<FixedOffset>,
+    pub observance: Observance,
+    pub day_type: DayType,
+    /// Worked fraction of the day, between 0.0 and 1.0. Days with a weight above zero count as
+    /// working days, `0.5` being a half day.
+    pub weight: f64,
+    pub name: Option<String>,
+}
+
+/// Whether the date is a day off or a make-up working day, counted even on a weekend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DayType {
+    #[default]
+    Holiday,
+    Workday,
+}
+
+/// How a holiday falling on a weekend is observed. Weekdays and weekends follow the weekend of
+/// the calendar, so `next_monday` is the first day after the weekend on a Friday/Saturday one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Observance {
+    /// The holiday is not moved.
+    #[default]
+    None,
+    /// Moved to the closest weekday, the following one on ties. Saturday to Friday and Sunday
+    /// to Monday on a Saturday/Sunday weekend.
+    NearestWeekday,
+    /// Moved to the first weekday after the weekend.
+    NextMonday,
+    /// Moved to the last weekday before the weekend.
+    PreviousFriday,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Invalid observance `{0}`. Expected `none`, `nearest_weekday`, `next_monday` or `previous_friday`.")]
+pub struct InvalidObservance(pub String);
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Invalid day type `{0}`. Expected `holiday` or `workday`.")]
+pub struct InvalidDayType(pub String);
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Invalid weight `{0}`. Expected a value between 0 and 1.")]
+pub struct InvalidWeight(pub String);
+
+impl Holiday {
+    pub fn new(date: Date<FixedOffset>, observance: Observance) -> Self {
+        Holiday {
+            date,
+            observance,
+            day_type: DayType::Holiday,
+            weight: 0.0,
+            name: None,
+        }
+    }
+
+    /// Make-up working day.
+    pub fn workday(date: Date<FixedOffset>) -> Self {
+        Holiday {
+            date,
+            observance: Observance::None,
+            day_type: DayType::Workday,
+            weight: 1.0,
+            name: None,
+        }
+    }
+
+    pub fn with_weight(self, weight: f64) -> Self {
+        Holiday { weight, ..self }
+    }
+
+    pub fn with_name(self, name: Option<String>) -> Self {
+        Holiday { name, ..self }
+    }
+
+    /// Whether the whole date is not worked. Only those are observed on other dates.
+    pub fn is_day_off(&self) -> bool {
+        self.day_type == DayType::Holiday && self.weight <= 0.0
+    }
+}
+
+impl From<Date<FixedOffset>> for Holiday {
+    fn from(date: Date<FixedOffset>) -> Self {
+        Holiday::new(date, Observance::None)
+    }
+}
+
+impl FromStr for Observance {
+    type Err = InvalidObservance;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "none" => Ok(Observance::None),
+            "nearest_weekday" => Ok(Observance::NearestWeekday),
+            "next_monday" => Ok(Observance::NextMonday),
+            "previous_friday" => Ok(Observance::PreviousFriday),
+            _ => Err(InvalidObservance(value.to_string())),
+        }
+    }
+}
+
+impl Display for Observance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            Observance::None => "none",
+            Observance::NearestWeekday => "nearest_weekday",
+            Observance::NextMonday => "next_monday",
+            Observance::PreviousFriday => "previous_friday",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+impl FromStr for DayType {
+    type Err = InvalidDayType;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "holiday" => Ok(DayType::Holiday),
+            "workday" => Ok(DayType::Workday),
+            _ => Err(InvalidDayType(value.to_string())),
+        }
+    }
+}
+
+/// Parses a weight between 0 and 1, inclusive.
+pub fn parse_weight(value: &str) -> Result<f64, InvalidWeight> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|weight| (0.0..=1.0).contains(weight))
+        .ok_or_else(|| InvalidWeight(value.to_string()))
+}
+
+impl Display for DayType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DayType::Holiday => write!(f, "holiday"),
+            DayType::Workday => write!(f, "workday"),
+        }
+    }
+}
+
+/// Dates that are not working days because of the holidays, along with the name of the holiday
+/// observed on each date. Holidays on a weekend are moved following their observance, skipping
+/// the dates already taken by other holidays. Make-up working days and partial holidays are
+/// ignored.
+pub fn observed_days_off(
+    holidays: &[Holiday],
+    weekend: Weekend,
+) -> BTreeMap<Date<FixedOffset>, Option<String>> {
+    let mut holidays: Vec<&Holiday> = holidays
+        .iter()
+        .filter(|holiday| holiday.is_day_off())
+        .collect();
+    holidays.sort_by_key(|holiday| holiday.date);
+
+    let mut days_off = BTreeMap::new();
+    for holiday in &holidays {
+        let name = days_off.entry(holiday.date).or_insert(None);
+        if name.is_none() {
+            *name = holiday.name.clone();
+        }
+    }
+
+    for holiday in holidays {
+        if holiday.observance == Observance::None || !weekend.contains(holiday.date.weekday()) {
+            continue;
+        }
+
+        if let Some(step) = observance_step(holiday, weekend) {
+            let mut date = holiday.date + step;
+            while weekend.contains(date.weekday()) || days_off.contains_key(&date) {
+                date += step;
+            }
+            let name = holiday
+                .name
+                .as_ref()
+                .map(|name| format!("{} (observed)", name));
+            days_off.insert(date, name);
+        }
+    }
+
+    days_off
+}
+
+/// Names of the days off, as well as of the named partial holidays and make-up working days.
+pub fn holiday_names(
+    holidays: &[Holiday],
+    days_off: &BTreeMap<Date<FixedOffset>, Option<String>>,
+) -> BTreeMap<Date<FixedOffset>, String> {
+    let mut names: BTreeMap<Date<FixedOffset>, String> = days_off
+        .iter()
+        .filter_map(|(date, name)| Some((*date, name.clone()?)))
+        .collect();
+
+    for holiday in holidays.iter().filter(|holiday| !holiday.is_day_off()) {
+        if let Some(name) = &holiday.name {
+            names.entry(holiday.date).or_insert_with(|| name.clone());
+        }
+    }
+
+    names
+}
+
+/// Make-up working days, sorted.
+pub fn workday_dates(holidays: &[Holiday]) -> Vec<Date<FixedOffset>> {
+    let dates: BTreeSet<Date<FixedOffset>> = holidays
+        .iter()
+        .filter(|holiday| holiday.day_type == DayType::Workday && holiday.weight > 0.0)
+        .map(|holiday| holiday.date)
+        .collect();
+    dates.into_iter().collect()
+}
+
+/// Weights of the dates that are worked, the lowest one when a date is listed twice.
+pub fn working_weights(holidays: &[Holiday]) -> BTreeMap<Date<FixedOffset>, f64> {
+    let mut weights = BTreeMap::new();
+    for holiday in holidays.iter().filter(|holiday| holiday.weight > 0.0) {
+        let weight = weights.entry(holiday.date).or_insert(holiday.weight);
+        *weight = holiday.weight.min(*weight);
+    }
+    weights
+}
+
+fn observance_step(holiday: &Holiday, weekend: Weekend) -> Option<Duration> {
+    let days_until_weekday = |step: i64| {
+        (1..=7)
+            .find(|days| !weekend.contains((holiday.date + Duration::days(step * days)).weekday()))
+    };
+
+    let after = days_until_weekday(1)?;
+    match holiday.observance {
+        Observance::None => None,
+        Observance::NextMonday => Some(Duration::days(1)),
+        Observance::PreviousFriday => Some(Duration::days(-1)),
+        Observance::NearestWeekday if days_until_weekday(-1)? < after => Some(Duration::days(-1)),
+        Observance::NearestWeekday => Some(Duration::days(1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Weekday};
+
+    use super::*;
+
+    fn observed(holidays: Vec<Holiday>, weekend: Weekend) -> Vec<Date<FixedOffset>> {
+        observed_days_off(&holidays, weekend).into_keys().collect()
+    }
+
+    #[test]
+    fn should_parse_and_display_observance() {
+        assert_eq!("none".parse::<Observance>(), Ok(Observance::None));
+        assert_eq!(
+            "Nearest_Weekday".parse::<Observance>(),
+            Ok(Observance::NearestWeekday)
+        );
+        assert_eq!(
+            "next_monday".parse::<Observance>(),
+            Ok(Observance::NextMonday)
+        );
+        assert_eq!(
+            "previous_friday".parse::<Observance>(),
+            Ok(Observance::PreviousFriday)
+        );
+        assert_eq!(
+            "monday".parse::<Observance>(),
+            Err(InvalidObservance("monday".to_string()))
+        );
+
+        assert_eq!(Observance::NearestWeekday.to_string(), "nearest_weekday");
+    }
+
+    #[test]
+    fn should_parse_and_display_day_type() {
+        assert_eq!("holiday".parse::<DayType>(), Ok(DayType::Holiday));
+        assert_eq!(" Workday".parse::<DayType>(), Ok(DayType::Workday));
+        assert_eq!(
+            "weekend".parse::<DayType>(),
+            Err(InvalidDayType("weekend".to_string()))
+        );
+
+        assert_eq!(DayType::Workday.to_string(), "workday");
+    }
+
+    #[test]
+    fn should_parse_weight() {
+        assert_eq!(parse_weight("0.5"), Ok(0.5));
+        assert_eq!(parse_weight(" 1"), Ok(1.0));
+        assert_eq!(parse_weight("1.5"), Err(InvalidWeight("1.5".to_string())));
+        assert_eq!(parse_weight("half"), Err(InvalidWeight("half".to_string())));
+    }
+
+    #[test]
+    fn should_not_observe_partial_holidays() {
+        let offset = FixedOffset::west(3 * 3600);
+        let holidays = vec![
+            Holiday::new(offset.ymd(2022, 12, 24), Observance::NextMonday).with_weight(0.5),
+            Holiday::new(offset.ymd(2022, 12, 23), Observance::None).with_weight(0.5),
+            Holiday::new(offset.ymd(2022, 12, 23), Observance::None).with_weight(0.25),
+        ];
+
+        assert_eq!(working_weights(&holidays).len(), 2);
+        assert_eq!(
+            working_weights(&holidays).get(&offset.ymd(2022, 12, 23)),
+            Some(&0.25)
+        );
+        assert!(observed(holidays, Weekend::default()).is_empty());
+    }
+
+    #[test]
+    fn should_name_observed_holidays() {
+        let offset = FixedOffset::west(3 * 3600);
+        let christmas = Some("Christmas Day".to_string());
+        let holidays = vec![
+            Holiday::new(offset.ymd(2021, 12, 25), Observance::NextMonday)
+                .with_name(christmas.clone()),
+            Holiday::new(offset.ymd(2021, 12, 24), Observance::None)
+                .with_name(Some("Christmas Eve".to_string()))
+                .with_weight(0.5),
+            offset.ymd(2021, 12, 31).into(),
+        ];
+
+        let days_off = observed_days_off(&holidays, Weekend::default());
+        assert_eq!(days_off.get(&offset.ymd(2021, 12, 25)), Some(&christmas));
+        assert_eq!(
+            days_off.get(&offset.ymd(2021, 12, 27)),
+            Some(&Some("Christmas Day (observed)".to_string()))
+        );
+        assert_eq!(days_off.get(&offset.ymd(2021, 12, 31)), Some(&None));
+
+        let names = holiday_names(&holidays, &days_off);
+        assert_eq!(names.len(), 3);
+        assert_eq!(
+            names.get(&offset.ymd(2021, 12, 24)).map(String::as_str),
+            Some("Christmas Eve")
+        );
+    }
+
+    #[test]
+    fn should_split_holidays_and_workdays() {
+        let offset = FixedOffset::west(3 * 3600);
+        let holidays = vec![
+            Holiday::workday(offset.ymd(2022, 10, 8)),
+            Holiday::new(offset.ymd(2022, 10, 3), Observance::None),
+            Holiday::new(offset.ymd(2022, 10, 1), Observance::NextMonday),
+        ];
+
+        assert_eq!(workday_dates(&holidays), vec![offset.ymd(2022, 10, 8)]);
+        assert_eq!(
+            observed(holidays, Weekend::default()),
+            vec![
+                offset.ymd(2022, 10, 1),
+                offset.ymd(2022, 10, 3),
+                offset.ymd(2022, 10, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_observe_weekend_holidays() {
+        let offset = FixedOffset::west(3 * 3600);
+        let observed =
+            |date, observance| observed(vec![Holiday::new(date, observance)], Weekend::default());
+
+        // Saturday, 2022-12-24
+        let saturday = offset.ymd(2022, 12, 24);
+        assert_eq!(observed(saturday, Observance::None), vec![saturday]);
+        assert_eq!(
+            observed(saturday, Observance::NearestWeekday),
+            vec![offset.ymd(2022, 12, 23), saturday]
+        );
+        assert_eq!(
+            observed(saturday, Observance::NextMonday),
+            vec![saturday, offset.ymd(2022, 12, 26)]
+        );
+        assert_eq!(
+            observed(saturday, Observance::PreviousFriday),
+            vec![offset.ymd(2022, 12, 23), saturday]
+        );
+
+        // Sunday, 2022-12-25
+        let sunday = offset.ymd(2022, 12, 25);
+        assert_eq!(
+            observed(sunday, Observance::NearestWeekday),
+            vec![sunday, offset.ymd(2022, 12, 26)]
+        );
+
+        // Weekdays are never moved
+        let monday = offset.ymd(2022, 12, 26);
+        assert_eq!(observed(monday, Observance::NextMonday), vec![monday]);
+    }
+
+    #[test]
+    fn should_skip_dates_taken_by_other_holidays() {
+        let offset = FixedOffset::west(3 * 3600);
+
+        // Christmas on Saturday and Boxing Day on Sunday, 2021
+        let holidays = vec![
+            Holiday::new(offset.ymd(2021, 12, 26), Observance::NextMonday),
+            Holiday::new(offset.ymd(2021, 12, 25), Observance::NextMonday),
+        ];
+
+        assert_eq!(
+            observed(holidays, Weekend::default()),
+            vec![
+                offset.ymd(2021, 12, 25),
+                offset.ymd(2021, 12, 26),
+                offset.ymd(2021, 12, 27),
+                offset.ymd(2021, 12, 28),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_observe_following_the_calendar_weekend() {
+        let offset = FixedOffset::west(3 * 3600);
+        let weekend = Weekend::new(&[Weekday::Fri, Weekday::Sat]);
+
+        // Friday, 2022-12-23
+        let friday = offset.ymd(2022, 12, 23);
+        assert_eq!(
+            observed(
+                vec![Holiday::new(friday, Observance::NearestWeekday)],
+                weekend
+            ),
+            vec![offset.ymd(2022, 12, 22), friday]
+        );
+        assert_eq!(
+            observed(vec![Holiday::new(friday, Observance::NextMonday)], weekend),
+            vec![friday, offset.ymd(2022, 12, 25)]
+        );
+    }
+}
@@ -0,0 +1,402 @@
+use std::str::FromStr;
+
+use chrono::{Date, Datelike, Duration, FixedOffset, NaiveDate, TimeZone, Weekday};
+use thiserror::Error;
+
+use crate::holiday::{Holiday, Observance};
+
+/// Recurring holiday, expanded into dates for any year.
+///
+/// Rules are written as:
+/// * `12-25`: fixed date, month and day.
+/// * `01-mon#3`: nth weekday of the month, third Monday of January. Negative values count from
+///   the end of the month, `05-mon#-1` being the last Monday of May.
+/// * `easter`, `easter-47`, `easter+60`: days relative to the Gregorian Easter Sunday.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HolidayRule {
+    Fixed { month: u32, day: u32 },
+    NthWeekday { month: u32, weekday: Weekday, n: i8 },
+    EasterOffset(i64),
+}
+
+/// Holiday rule along with how the holidays it produces are observed and weighted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecurringHoliday {
+    pub rule: HolidayRule,
+    pub observance: Observance,
+    pub weight: f64,
+    pub name: Option<String>,
+    /// First day the holiday may happen, every year when `None`.
+    pub since: Option<NaiveDate>,
+    /// Years between occurrences, counted from `since`. `0` is taken as every year.
+    pub interval: u32,
+    /// Consecutive days off of each occurrence.
+    pub days: i64,
+    /// Occurrences that do not happen.
+    pub exceptions: Vec<NaiveDate>,
+}
+
+/// Days an Easter offset may be away from the Easter Sunday.
+const MAX_EASTER_OFFSET: i64 = 366;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Invalid holiday rule `{0}`.")]
+pub struct InvalidHolidayRule(pub String);
+
+impl HolidayRule {
+    /// Date of the holiday in the year, if it happens that year (e.g. `02-29`).
+    pub fn date(&self, time_offset: FixedOffset, year: i32) -> Option<Date<FixedOffset>> {
+        let date = match self {
+            HolidayRule::Fixed { month, day } => NaiveDate::from_ymd_opt(year, *month, *day),
+            HolidayRule::NthWeekday { month, weekday, n } => {
+                nth_weekday(year, *month, *weekday, *n)
+            }
+            HolidayRule::EasterOffset(days) => easter_sunday(year)
+                .and_then(|easter| easter.checked_add_signed(Duration::days(*days))),
+        }?;
+
+        Some(time_offset.from_utc_date(&date))
+    }
+}
+
+impl RecurringHoliday {
+    pub fn new(rule: HolidayRule, observance: Observance) -> Self {
+        RecurringHoliday {
+            rule,
+            observance,
+            weight: 0.0,
+            name: None,
+            since: None,
+            interval: 1,
+            days: 1,
+            exceptions: Vec::new(),
+        }
+    }
+
+    pub fn with_weight(self, weight: f64) -> Self {
+        RecurringHoliday { weight, ..self }
+    }
+
+    pub fn with_name(self, name: Option<String>) -> Self {
+        RecurringHoliday { name, ..self }
+    }
+
+    /// Holidays of the rule in the year, one for each day of the occurrence.
+    fn holidays(&self, time_offset: FixedOffset, year: i32) -> Vec<Holiday> {
+        let date = match self.rule.date(time_offset, year) {
+            Some(date) if self.happens_on(date.naive_utc()) => date,
+            _ => return Vec::new(),
+        };
+
+        (0..self.days)
+            .filter_map(|day| date.checked_add_signed(Duration::days(day)))
+            .map(|date| {
+                Holiday::new(date, self.observance)
+                    .with_weight(self.weight)
+                    .with_name(self.name.clone())
+            })
+            .collect()
+    }
+
+    fn happens_on(&self, date: NaiveDate) -> bool {
+        !self.exceptions.contains(&date)
+            && self.since.is_none_or(|since| {
+                let years = i64::from(date.year() - since.year());
+                date >= since && years % i64::from(self.interval.max(1)) == 0
+            })
+    }
+}
+
+impl From<HolidayRule> for RecurringHoliday {
+    fn from(rule: HolidayRule) -> Self {
+        RecurringHoliday::new(rule, Observance::None)
+    }
+}
+
+impl FromStr for HolidayRule {
+    type Err = InvalidHolidayRule;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidHolidayRule(value.to_string());
+        let rule = value.trim().to_lowercase();
+
+        if let Some(offset) = rule.strip_prefix("easter") {
+            let days = if offset.is_empty() {
+                0
+            } else {
+                offset
+                    .trim_start_matches('+')
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|days| days.abs() <= MAX_EASTER_OFFSET)
+                    .ok_or_else(invalid)?
+            };
+            return Ok(HolidayRule::EasterOffset(days));
+        }
+
+        let (month, rest) = rule.split_once('-').ok_or_else(invalid)?;
+        let month = month.parse::<u32>().map_err(|_| invalid())?;
+        if !(1..=12).contains(&month) {
+            return Err(invalid());
+        }
+
+        match rest.split_once('#') {
+            None => {
+                let day = rest.parse::<u32>().map_err(|_| invalid())?;
+                NaiveDate::from_ymd_opt(2000, month, day).ok_or_else(invalid)?;
+                Ok(HolidayRule::Fixed { month, day })
+            }
+            Some((weekday, n)) => {
+                let weekday = weekday.parse::<Weekday>().map_err(|_| invalid())?;
+                let n = n.parse::<i8>().map_err(|_| invalid())?;
+                if n == 0 || !(-5..=5).contains(&n) {
+                    return Err(invalid());
+                }
+                Ok(HolidayRule::NthWeekday { month, weekday, n })
+            }
+        }
+    }
+}
+
+/// Holidays of every rule between the first and the last year, inclusive, sorted by date.
+pub fn expand(
+    rules: &[RecurringHoliday],
+    time_offset: FixedOffset,
+    first_year: i32,
+    last_year: i32,
+) -> Vec<Holiday> {
+    let mut holidays: Vec<Holiday> = (first_year..=last_year)
+        .flat_map(|year| {
+            rules
+                .iter()
+                .flat_map(move |recurring| recurring.holidays(time_offset, year))
+        })
+        .collect();
+
+    holidays.sort_by_key(|holiday| holiday.date);
+    holidays
+}
+
+fn nth_weekday(year: i32, month: u32, weekday: Weekday, n: i8) -> Option<NaiveDate> {
+    if n > 0 {
+        let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+        let days_until =
+            (7 + weekday.num_days_from_monday() - first.weekday().num_days_from_monday()) % 7;
+        let date = first + Duration::days((days_until + 7 * (n as u32 - 1)) as i64);
+        Some(date).filter(|date| date.month() == month)
+    } else {
+        let last = last_day_of_month(year, month)?;
+        let days_since =
+            (7 + last.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+        let date = last - Duration::days((days_since + 7 * (n.unsigned_abs() as u32 - 1)) as i64);
+        Some(date).filter(|date| date.month() == month)
+    }
+}
+
+fn last_day_of_month(year: i32, month: u32) -> Option<NaiveDate> {
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    NaiveDate::from_ymd_opt(next_year, next_month, 1).map(|date| date - Duration::days(1))
+}
+
+/// Anonymous Gregorian algorithm (Meeus/Jones/Butcher).
+fn easter_sunday(year: i32) -> Option<NaiveDate> {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_rules() {
+        assert_eq!(
+            "12-25".parse::<HolidayRule>(),
+            Ok(HolidayRule::Fixed { month: 12, day: 25 })
+        );
+        assert_eq!(
+            "01-mon#3".parse::<HolidayRule>(),
+            Ok(HolidayRule::NthWeekday {
+                month: 1,
+                weekday: Weekday::Mon,
+                n: 3
+            })
+        );
+        assert_eq!(
+            "05-Monday#-1".parse::<HolidayRule>(),
+            Ok(HolidayRule::NthWeekday {
+                month: 5,
+                weekday: Weekday::Mon,
+                n: -1
+            })
+        );
+        assert_eq!(
+            "easter".parse::<HolidayRule>(),
+            Ok(HolidayRule::EasterOffset(0))
+        );
+        assert_eq!(
+            "easter-47".parse::<HolidayRule>(),
+            Ok(HolidayRule::EasterOffset(-47))
+        );
+        assert_eq!(
+            "Easter+60".parse::<HolidayRule>(),
+            Ok(HolidayRule::EasterOffset(60))
+        );
+
+        for invalid in [
+            "jose",
+            "13-01",
+            "02-30",
+            "01-mon#0",
+            "01-mon#6",
+            "easter*2",
+            "easter+367",
+            "easter+1000000000",
+        ] {
+            assert_eq!(
+                invalid.parse::<HolidayRule>(),
+                Err(InvalidHolidayRule(invalid.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn should_calculate_rule_dates() {
+        let offset = FixedOffset::west(3 * 3600);
+
+        assert_eq!(
+            HolidayRule::Fixed { month: 2, day: 29 }.date(offset, 2024),
+            Some(offset.ymd(2024, 2, 29))
+        );
+        assert_eq!(
+            HolidayRule::Fixed { month: 2, day: 29 }.date(offset, 2023),
+            None
+        );
+
+        let martin_luther_king = HolidayRule::NthWeekday {
+            month: 1,
+            weekday: Weekday::Mon,
+            n: 3,
+        };
+        assert_eq!(
+            martin_luther_king.date(offset, 2023),
+            Some(offset.ymd(2023, 1, 16))
+        );
+        assert_eq!(
+            martin_luther_king.date(offset, 2024),
+            Some(offset.ymd(2024, 1, 15))
+        );
+
+        let memorial_day = HolidayRule::NthWeekday {
+            month: 5,
+            weekday: Weekday::Mon,
+            n: -1,
+        };
+        assert_eq!(
+            memorial_day.date(offset, 2023),
+            Some(offset.ymd(2023, 5, 29))
+        );
+        assert_eq!(
+            memorial_day.date(offset, 2021),
+            Some(offset.ymd(2021, 5, 31))
+        );
+
+        let fifth_monday = HolidayRule::NthWeekday {
+            month: 2,
+            weekday: Weekday::Mon,
+            n: 5,
+        };
+        assert_eq!(fifth_monday.date(offset, 2023), None);
+
+        let carnival = HolidayRule::EasterOffset(-47);
+        assert_eq!(carnival.date(offset, 2022), Some(offset.ymd(2022, 3, 1)));
+        assert_eq!(carnival.date(offset, 2023), Some(offset.ymd(2023, 2, 21)));
+
+        let corpus_christi = HolidayRule::EasterOffset(60);
+        assert_eq!(
+            corpus_christi.date(offset, 2022),
+            Some(offset.ymd(2022, 6, 16))
+        );
+        assert_eq!(
+            corpus_christi.date(offset, 2024),
+            Some(offset.ymd(2024, 5, 30))
+        );
+    }
+
+    #[test]
+    fn should_expand_rules_for_every_year() {
+        let offset = FixedOffset::west(3 * 3600);
+        let rules = vec![
+            RecurringHoliday::new(
+                HolidayRule::Fixed { month: 12, day: 25 },
+                Observance::NextMonday,
+            ),
+            HolidayRule::EasterOffset(-2).into(),
+        ];
+
+        let holidays = expand(&rules, offset, 2022, 2023);
+
+        assert_eq!(
+            holidays,
+            vec![
+                Holiday::new(offset.ymd(2022, 4, 15), Observance::None),
+                Holiday::new(offset.ymd(2022, 12, 25), Observance::NextMonday),
+                Holiday::new(offset.ymd(2023, 4, 7), Observance::None),
+                Holiday::new(offset.ymd(2023, 12, 25), Observance::NextMonday),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_expand_rules_since_their_first_day() {
+        let offset = FixedOffset::west(3 * 3600);
+        let rules = vec![RecurringHoliday {
+            since: Some(NaiveDate::from_ymd(2021, 12, 24)),
+            interval: 2,
+            days: 2,
+            exceptions: vec![NaiveDate::from_ymd(2025, 12, 24)],
+            ..HolidayRule::Fixed { month: 12, day: 24 }.into()
+        }];
+
+        let dates: Vec<_> = expand(&rules, offset, 2020, 2027)
+            .into_iter()
+            .map(|holiday| holiday.date)
+            .collect();
+
+        assert_eq!(
+            dates,
+            vec![
+                offset.ymd(2021, 12, 24),
+                offset.ymd(2021, 12, 25),
+                offset.ymd(2023, 12, 24),
+                offset.ymd(2023, 12, 25),
+                offset.ymd(2027, 12, 24),
+                offset.ymd(2027, 12, 25),
+            ]
+        );
+
+        let every_year = vec![RecurringHoliday {
+            interval: 0,
+            ..rules[0].clone()
+        }];
+        assert_eq!(expand(&every_year, offset, 2020, 2027).len(), 12);
+    }
+}
@@ -1,51 +1,437 @@
-use chrono::{Date, FixedOffset, NaiveDate, ParseError, TimeZone};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use chrono::{FixedOffset, NaiveDate, ParseError, TimeZone};
 use csv::StringRecord;
 use thiserror::Error;
-use tracing::error;
+use tracing::warn;
 
-use crate::holidays_loader::HolidaysLoaderError::{ErrorOpeningFile, InvalidDateFormat};
+use crate::holiday;
+use crate::holiday::{DayType, Holiday, Observance};
+use crate::holiday_rules::{HolidayRule, InvalidHolidayRule, RecurringHoliday};
+use crate::holidays_loader::HolidaysLoaderError::{
+    DateOutOfOrder, DuplicateDate, EmptyFile, ErrorOpeningFile, ErrorReadingFile,
+    InvalidDateFormat, InvalidDayType, InvalidEvent, InvalidHolidaysFile, InvalidObservance,
+    InvalidRow, InvalidRule, InvalidWeight,
+};
+use crate::ics;
+use crate::ics::IcsError;
 
 #[derive(Error, Debug)]
 pub enum HolidaysLoaderError {
     #[error("Error opening file {0}.")]
     ErrorOpeningFile(String, #[source] csv::Error),
+    #[error("Error reading file {0}.")]
+    ErrorReadingFile(String, #[source] std::io::Error),
+    #[error("Invalid row at line {0}.")]
+    InvalidRow(u64, #[source] csv::Error),
     #[error("Invalid date format at line {0}.")]
     InvalidDateFormat(u64, #[source] ParseError),
+    #[error("Invalid holiday rule at line {0}.")]
+    InvalidRule(u64, #[source] InvalidHolidayRule),
+    #[error("Invalid observance at line {0}.")]
+    InvalidObservance(u64, #[source] holiday::InvalidObservance),
+    #[error("Invalid day type at line {0}.")]
+    InvalidDayType(u64, #[source] holiday::InvalidDayType),
+    #[error("Invalid weight at line {0}.")]
+    InvalidWeight(u64, #[source] holiday::InvalidWeight),
+    #[error("Invalid event at line {0}.")]
+    InvalidEvent(u64, #[source] IcsError),
+    #[error("Duplicate date {1} at line {0}.")]
+    DuplicateDate(u64, NaiveDate),
+    #[error("Date {1} out of order at line {0}.")]
+    DateOutOfOrder(u64, NaiveDate),
+    #[error("No holidays in file {0}.")]
+    EmptyFile(String),
+    #[error("Invalid holidays file {0}. {}", join_problems(.1))]
+    InvalidHolidaysFile(String, Vec<HolidaysLoaderError>),
+}
+
+/// How problems in the holidays and rules CSVs are handled. `Strict` fails with every problem
+/// found, `Lenient` logs them and skips the invalid rows and duplicate dates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoadMode {
+    Strict,
+    #[default]
+    Lenient,
+}
+
+/// Format of a holidays file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HolidaysFormat {
+    Csv,
+    /// iCalendar file, reading its all-day events.
+    Ics,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Invalid holidays format `{0}`. Expected `csv` or `ics`.")]
+pub struct InvalidHolidaysFormat(pub String);
+
+impl HolidaysFormat {
+    /// Format given by the extension of the file, `.ics` and `.ical` being iCalendar files and
+    /// anything else a CSV.
+    pub fn of(holidays_file: &str) -> Self {
+        match Path::new(holidays_file)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase)
+            .as_deref()
+        {
+            Some("ics") | Some("ical") => HolidaysFormat::Ics,
+            _ => HolidaysFormat::Csv,
+        }
+    }
+}
+
+impl FromStr for HolidaysFormat {
+    type Err = InvalidHolidaysFormat;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "csv" => Ok(HolidaysFormat::Csv),
+            "ics" => Ok(HolidaysFormat::Ics),
+            _ => Err(InvalidHolidaysFormat(value.to_string())),
+        }
+    }
+}
+
+impl Display for HolidaysFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HolidaysFormat::Csv => write!(f, "csv"),
+            HolidaysFormat::Ics => write!(f, "ics"),
+        }
+    }
+}
+
+/// Holidays of a holidays file, along with the rules of iCalendar events recurring without an
+/// end, expanded for any year needed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LoadedHolidays {
+    pub holidays: Vec<Holiday>,
+    pub rules: Vec<RecurringHoliday>,
 }
 
+/// Loads the holidays file in the given format.
+pub fn load_with_format(
+    format: HolidaysFormat,
+    time_offset: FixedOffset,
+    holidays_file: &str,
+    observance: Observance,
+    mode: LoadMode,
+) -> Result<LoadedHolidays, HolidaysLoaderError> {
+    match format {
+        HolidaysFormat::Csv => Ok(LoadedHolidays {
+            holidays: load(time_offset, holidays_file, observance, mode)?,
+            rules: Vec::new(),
+        }),
+        HolidaysFormat::Ics => load_ics(time_offset, holidays_file, observance, mode),
+    }
+}
+
+/// Loads the holidays CSV. The date is read from the first column, the optional `observance`
+/// column overrides the given observance, the optional `type` column marks make-up working
+/// days with `workday`, the optional `weight` column sets the worked fraction of the day and
+/// the optional `name` column names the holiday. Dates are expected in order and only once.
 pub fn load(
     time_offset: FixedOffset,
     holidays_file: &str,
-) -> Result<Vec<Date<FixedOffset>>, HolidaysLoaderError> {
+    observance: Observance,
+    mode: LoadMode,
+) -> Result<Vec<Holiday>, HolidaysLoaderError> {
     let mut holidays = Vec::new();
+    let mut problems = Vec::new();
+    let mut dates = HashSet::new();
+    let mut latest = None;
 
     let mut reader = csv::Reader::from_path(holidays_file)
         .map_err(|err| ErrorOpeningFile(holidays_file.to_string(), err))?;
+    let columns = Columns {
+        observance: optional_column(&mut reader, holidays_file, "observance")?,
+        day_type: optional_column(&mut reader, holidays_file, "type")?,
+        weight: optional_column(&mut reader, holidays_file, "weight")?,
+        name: optional_column(&mut reader, holidays_file, "name")?,
+    };
 
     for result in reader.records() {
-        match result {
-            Ok(record) => {
-                if let Some(date_string) = record.get(0) {
-                    let date = NaiveDate::parse_from_str(date_string, "%Y-%m-%d")
-                        .map_err(|err| InvalidDateFormat(line_number(record), err))?;
-                    holidays.push(time_offset.from_utc_date(&date));
-                }
+        let record = match result {
+            Ok(record) => record,
+            Err(err) => {
+                let line = err.position().map(|pos| pos.line()).unwrap_or_default();
+                problems.push(InvalidRow(line, err));
+                continue;
+            }
+        };
+        let line = line_number(&record);
+        let holiday = match read_holiday(&record, time_offset, &columns, observance) {
+            Ok(Some(holiday)) => holiday,
+            Ok(None) => continue,
+            Err(err) => {
+                problems.push(err);
+                continue;
             }
-            Err(err) => error!(
-                "Error {} loading holidays from {}",
-                err.to_string(),
-                holidays_file
-            ),
+        };
+
+        let date = holiday.date.naive_utc();
+        if !dates.insert(date) {
+            problems.push(DuplicateDate(line, date));
+            continue;
+        }
+        match latest {
+            Some(latest) if latest > date => problems.push(DateOutOfOrder(line, date)),
+            _ => latest = Some(date),
+        }
+        holidays.push(holiday);
+    }
+
+    check_problems(holidays_file, holidays, problems, mode)
+}
+
+/// Loads the all-day events of an iCalendar file, named after their summary. Each day of
+/// multi-day events is a holiday and events sharing a day are merged. Yearly recurrences without
+/// an end become holiday rules.
+fn load_ics(
+    time_offset: FixedOffset,
+    holidays_file: &str,
+    observance: Observance,
+    mode: LoadMode,
+) -> Result<LoadedHolidays, HolidaysLoaderError> {
+    let content = fs::read_to_string(holidays_file)
+        .map_err(|err| ErrorReadingFile(holidays_file.to_string(), err))?;
+    let mut problems = Vec::new();
+    let mut events = Vec::new();
+    for (line, event) in ics::read_events(&content) {
+        match event {
+            Ok(event) => events.push(event),
+            Err(err) => problems.push(InvalidEvent(line, err)),
+        }
+    }
+    let events = check_problems(holidays_file, events, problems, mode)?;
+
+    let mut dates = BTreeMap::new();
+    for event in &events {
+        for date in event.dates() {
+            dates.entry(date).or_insert_with(|| event.summary.clone());
         }
     }
 
-    Ok(holidays)
+    Ok(LoadedHolidays {
+        holidays: dates
+            .into_iter()
+            .map(|(date, name)| {
+                Holiday::new(time_offset.from_utc_date(&date), observance).with_name(name)
+            })
+            .collect(),
+        rules: events
+            .iter()
+            .filter_map(|event| event.recurring_holiday(observance))
+            .collect(),
+    })
 }
 
-fn line_number(record: StringRecord) -> u64 {
+/// Fails with every problem in strict mode, logs them otherwise.
+fn check_problems<T>(
+    holidays_file: &str,
+    holidays: Vec<T>,
+    mut problems: Vec<HolidaysLoaderError>,
+    mode: LoadMode,
+) -> Result<Vec<T>, HolidaysLoaderError> {
+    if holidays.is_empty() {
+        problems.push(EmptyFile(holidays_file.to_string()));
+    }
+
+    match mode {
+        LoadMode::Strict if !problems.is_empty() => {
+            Err(InvalidHolidaysFile(holidays_file.to_string(), problems))
+        }
+        _ => {
+            for problem in &problems {
+                warn!("{} In holidays file {}.", problem, holidays_file);
+            }
+            if !problems.is_empty() {
+                warn!(
+                    "Found {} problems in holidays file {}, invalid rows, events and duplicate dates were skipped.",
+                    problems.len(),
+                    holidays_file
+                );
+            }
+            Ok(holidays)
+        }
+    }
+}
+
+/// Optional columns of the holidays and rules CSVs.
+struct Columns {
+    observance: Option<usize>,
+    day_type: Option<usize>,
+    weight: Option<usize>,
+    name: Option<usize>,
+}
+
+fn read_holiday(
+    record: &StringRecord,
+    time_offset: FixedOffset,
+    columns: &Columns,
+    observance: Observance,
+) -> Result<Option<Holiday>, HolidaysLoaderError> {
+    let date_string = match record.get(0) {
+        None => return Ok(None),
+        Some(date_string) => date_string,
+    };
+    let date = NaiveDate::parse_from_str(date_string, "%Y-%m-%d")
+        .map_err(|err| InvalidDateFormat(line_number(record), err))?;
+    let date = time_offset.from_utc_date(&date);
+    let holiday = match read_day_type(record, columns.day_type)? {
+        DayType::Holiday => {
+            let observance = read_observance(record, columns.observance, observance)?;
+            Holiday::new(date, observance)
+        }
+        DayType::Workday => Holiday::workday(date),
+    }
+    .with_name(read_name(record, columns.name));
+
+    Ok(Some(match read_weight(record, columns.weight)? {
+        Some(weight) => holiday.with_weight(weight),
+        None => holiday,
+    }))
+}
+
+fn join_problems(problems: &[HolidaysLoaderError]) -> String {
+    problems
+        .iter()
+        .map(HolidaysLoaderError::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Loads the holiday rules CSV. The rule is read from the first column, the optional
+/// `observance` column overrides the given observance, the optional `weight` column sets the
+/// worked fraction of the day and the optional `name` column names the holiday. Problems are
+/// handled as in the holidays CSV.
+pub fn load_rules(
+    rules_file: &str,
+    observance: Observance,
+    mode: LoadMode,
+) -> Result<Vec<RecurringHoliday>, HolidaysLoaderError> {
+    let mut rules = Vec::new();
+    let mut problems = Vec::new();
+
+    let mut reader = csv::Reader::from_path(rules_file)
+        .map_err(|err| ErrorOpeningFile(rules_file.to_string(), err))?;
+    let columns = Columns {
+        observance: optional_column(&mut reader, rules_file, "observance")?,
+        day_type: None,
+        weight: optional_column(&mut reader, rules_file, "weight")?,
+        name: optional_column(&mut reader, rules_file, "name")?,
+    };
+
+    for result in reader.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(err) => {
+                let line = err.position().map(|pos| pos.line()).unwrap_or_default();
+                problems.push(InvalidRow(line, err));
+                continue;
+            }
+        };
+        match read_rule(&record, &columns, observance) {
+            Ok(Some(rule)) => rules.push(rule),
+            Ok(None) => {}
+            Err(err) => problems.push(err),
+        }
+    }
+
+    check_problems(rules_file, rules, problems, mode)
+}
+
+fn read_rule(
+    record: &StringRecord,
+    columns: &Columns,
+    observance: Observance,
+) -> Result<Option<RecurringHoliday>, HolidaysLoaderError> {
+    let rule = match record.get(0) {
+        None => return Ok(None),
+        Some(rule_string) => rule_string
+            .parse::<HolidayRule>()
+            .map_err(|err| InvalidRule(line_number(record), err))?,
+    };
+    let observance = read_observance(record, columns.observance, observance)?;
+    let weight = read_weight(record, columns.weight)?.unwrap_or_default();
+
+    Ok(Some(
+        RecurringHoliday::new(rule, observance)
+            .with_weight(weight)
+            .with_name(read_name(record, columns.name)),
+    ))
+}
+
+fn optional_column<R: std::io::Read>(
+    reader: &mut csv::Reader<R>,
+    file: &str,
+    name: &str,
+) -> Result<Option<usize>, HolidaysLoaderError> {
+    let headers = reader
+        .headers()
+        .map_err(|err| ErrorOpeningFile(file.to_string(), err))?;
+    Ok(headers
+        .iter()
+        .position(|header| header.trim().eq_ignore_ascii_case(name)))
+}
+
+fn read_observance(
+    record: &StringRecord,
+    column: Option<usize>,
+    default: Observance,
+) -> Result<Observance, HolidaysLoaderError> {
+    match column.and_then(|column| record.get(column)) {
+        Some(value) if !value.trim().is_empty() => value
+            .parse::<Observance>()
+            .map_err(|err| InvalidObservance(line_number(record), err)),
+        _ => Ok(default),
+    }
+}
+
+fn read_day_type(
+    record: &StringRecord,
+    column: Option<usize>,
+) -> Result<DayType, HolidaysLoaderError> {
+    match column.and_then(|column| record.get(column)) {
+        Some(value) if !value.trim().is_empty() => value
+            .parse::<DayType>()
+            .map_err(|err| InvalidDayType(line_number(record), err)),
+        _ => Ok(DayType::Holiday),
+    }
+}
+
+fn read_weight(
+    record: &StringRecord,
+    column: Option<usize>,
+) -> Result<Option<f64>, HolidaysLoaderError> {
+    match column.and_then(|column| record.get(column)) {
+        Some(value) if !value.trim().is_empty() => holiday::parse_weight(value)
+            .map(Some)
+            .map_err(|err| InvalidWeight(line_number(record), err)),
+        _ => Ok(None),
+    }
+}
+
+fn read_name(record: &StringRecord, column: Option<usize>) -> Option<String> {
+    column
+        .and_then(|column| record.get(column))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+}
+
+/// Line of the record in the file. CSV positions are 1-based, header included.
+fn line_number(record: &StringRecord) -> u64 {
     match record.position() {
         None => 0,
-        Some(pos) => pos.line() + 1,
+        Some(pos) => pos.line(),
     }
 }
 
@@ -53,13 +439,22 @@ fn line_number(record: StringRecord) -> u64 {
 mod tests {
     use chrono::{FixedOffset, TimeZone};
 
-    use crate::holidays_loader::load;
+    use crate::holiday::{DayType, Observance};
+    use crate::holiday_rules::{expand, HolidayRule};
+    use crate::holidays_loader::{
+        load, load_rules, load_with_format, HolidaysFormat, LoadMode, LoadedHolidays,
+    };
 
     #[tokio::test]
     async fn should_return_error_if_holidays_file_not_found() {
         let offset = FixedOffset::west(3 * 3600);
 
-        let result = load(offset, "unknown_file.csv");
+        let result = load(
+            offset,
+            "unknown_file.csv",
+            Observance::None,
+            LoadMode::Strict,
+        );
         assert!(result.is_err());
         assert_eq!(
             result.unwrap_err().to_string(),
@@ -71,11 +466,211 @@ mod tests {
     async fn should_return_error_if_any_invalid_date() {
         let offset = FixedOffset::west(3 * 3600);
 
-        let result = load(offset, "tests_resources/invalid_date_holidays.csv");
+        let result = load(
+            offset,
+            "tests_resources/invalid_date_holidays.csv",
+            Observance::None,
+            LoadMode::Strict,
+        );
         assert!(result.is_err());
         assert_eq!(
             result.unwrap_err().to_string(),
-            "Invalid date format at line 5."
+            "Invalid holidays file tests_resources/invalid_date_holidays.csv. Invalid date format at line 5."
+        );
+    }
+
+    #[tokio::test]
+    async fn should_report_file_line_numbers() {
+        let offset = FixedOffset::west(3 * 3600);
+
+        // The invalid date is on the 3rd line of the file, after the header
+        let result = load(
+            offset,
+            "tests_resources/line_number_holidays.csv",
+            Observance::None,
+            LoadMode::Strict,
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid holidays file tests_resources/line_number_holidays.csv. Invalid date format at line 3."
+        );
+    }
+
+    #[tokio::test]
+    async fn should_load_holiday_rules() {
+        let result = load_rules(
+            "tests_resources/holiday_rules.csv",
+            Observance::None,
+            LoadMode::Strict,
+        );
+        assert!(result.is_ok());
+
+        let rules = result.unwrap();
+        assert_eq!(rules.len(), 10);
+        assert_eq!(
+            rules.first().unwrap().rule,
+            HolidayRule::Fixed { month: 1, day: 1 }
+        );
+        assert_eq!(rules.get(1).unwrap().rule, HolidayRule::EasterOffset(-48));
+        assert_eq!(rules.get(1).unwrap().observance, Observance::None);
+
+        let result = load_rules(
+            "tests_resources/invalid_holiday_rules.csv",
+            Observance::None,
+            LoadMode::Strict,
+        );
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid holidays file tests_resources/invalid_holiday_rules.csv. Invalid holiday rule at line 3."
+        );
+
+        let result = load_rules(
+            "tests_resources/invalid_holiday_rules.csv",
+            Observance::None,
+            LoadMode::Lenient,
+        );
+        let rules: Vec<_> = result.unwrap().into_iter().map(|rule| rule.rule).collect();
+        assert_eq!(
+            rules,
+            vec![
+                HolidayRule::Fixed { month: 1, day: 1 },
+                HolidayRule::Fixed { month: 12, day: 25 }
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_load_observance_column() {
+        let offset = FixedOffset::west(3 * 3600);
+
+        let result = load(
+            offset,
+            "tests_resources/observance_holidays.csv",
+            Observance::NearestWeekday,
+            LoadMode::Strict,
+        );
+        assert!(result.is_ok());
+
+        let holidays = result.unwrap();
+        assert_eq!(holidays.len(), 3);
+        assert_eq!(
+            holidays.first().unwrap().observance,
+            Observance::NearestWeekday
+        );
+        assert_eq!(holidays.get(1).unwrap().observance, Observance::NextMonday);
+        assert_eq!(holidays.get(2).unwrap().observance, Observance::None);
+
+        let result = load(
+            offset,
+            "tests_resources/invalid_observance_holidays.csv",
+            Observance::None,
+            LoadMode::Strict,
+        );
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid holidays file tests_resources/invalid_observance_holidays.csv. Invalid observance at line 3."
+        );
+    }
+
+    #[tokio::test]
+    async fn should_load_type_column() {
+        let offset = FixedOffset::west(3 * 3600);
+
+        let result = load(
+            offset,
+            "tests_resources/workday_holidays.csv",
+            Observance::NextMonday,
+            LoadMode::Strict,
+        );
+        assert!(result.is_ok());
+
+        let holidays = result.unwrap();
+        assert_eq!(holidays.len(), 3);
+        assert_eq!(holidays.first().unwrap().day_type, DayType::Holiday);
+        assert_eq!(holidays.first().unwrap().observance, Observance::NextMonday);
+        assert_eq!(holidays.get(1).unwrap().day_type, DayType::Workday);
+        assert_eq!(holidays.get(1).unwrap().date, offset.ymd(2022, 10, 8));
+        assert_eq!(holidays.get(2).unwrap().day_type, DayType::Holiday);
+
+        let result = load(
+            offset,
+            "tests_resources/invalid_type_holidays.csv",
+            Observance::None,
+            LoadMode::Strict,
+        );
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid holidays file tests_resources/invalid_type_holidays.csv. Invalid day type at line 4."
+        );
+    }
+
+    #[tokio::test]
+    async fn should_load_weight_column() {
+        let offset = FixedOffset::west(3 * 3600);
+
+        let result = load(
+            offset,
+            "tests_resources/weighted_holidays.csv",
+            Observance::None,
+            LoadMode::Strict,
+        );
+        assert!(result.is_ok());
+
+        let holidays = result.unwrap();
+        assert_eq!(holidays.len(), 3);
+        assert_eq!(holidays.first().unwrap().weight, 0.5);
+        assert_eq!(holidays.get(1).unwrap().weight, 0.0);
+        assert_eq!(holidays.get(2).unwrap().weight, 1.0);
+
+        let result = load_rules(
+            "tests_resources/weighted_holiday_rules.csv",
+            Observance::None,
+            LoadMode::Strict,
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().get(1).unwrap().weight, 0.5);
+
+        let result = load(
+            offset,
+            "tests_resources/invalid_weight_holidays.csv",
+            Observance::None,
+            LoadMode::Strict,
+        );
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().to_string(), "Invalid holidays file tests_resources/invalid_weight_holidays.csv. Invalid weight at line 3.");
+    }
+
+    #[tokio::test]
+    async fn should_load_name_column() {
+        let offset = FixedOffset::west(3 * 3600);
+
+        let result = load(
+            offset,
+            "tests_resources/named_holidays.csv",
+            Observance::None,
+            LoadMode::Strict,
+        );
+        assert!(result.is_ok());
+
+        let holidays = result.unwrap();
+        assert_eq!(holidays.len(), 3);
+        assert_eq!(
+            holidays.get(1).unwrap().name.as_deref(),
+            Some("Proclamação da República")
+        );
+        assert_eq!(holidays.get(2).unwrap().name, None);
+
+        let result = load_rules(
+            "tests_resources/holiday_rules.csv",
+            Observance::None,
+            LoadMode::Strict,
+        );
+        assert_eq!(
+            result.unwrap().first().unwrap().name.as_deref(),
+            Some("Confraternização Universal")
         );
     }
 
@@ -83,25 +678,143 @@ mod tests {
     async fn should_load_holidays_ignoring_offset() {
         let offset = FixedOffset::west(3 * 3600);
 
-        let result = load(offset, "tests_resources/small_holidays.csv");
+        let result = load(
+            offset,
+            "tests_resources/small_holidays.csv",
+            Observance::None,
+            LoadMode::Strict,
+        );
         assert!(result.is_ok());
 
         let holidays = result.unwrap();
         assert_eq!(holidays.len(), 12);
 
-        println!("{}", holidays.get(0).unwrap());
-
-        assert_eq!(*holidays.get(0).unwrap(), offset.ymd(2020, 1, 1));
-        assert_eq!(*holidays.get(1).unwrap(), offset.ymd(2020, 2, 24));
-        assert_eq!(*holidays.get(2).unwrap(), offset.ymd(2020, 2, 25));
-        assert_eq!(*holidays.get(3).unwrap(), offset.ymd(2020, 4, 10));
-        assert_eq!(*holidays.get(4).unwrap(), offset.ymd(2020, 4, 21));
-        assert_eq!(*holidays.get(5).unwrap(), offset.ymd(2020, 5, 1));
-        assert_eq!(*holidays.get(6).unwrap(), offset.ymd(2020, 6, 11));
-        assert_eq!(*holidays.get(7).unwrap(), offset.ymd(2020, 9, 7));
-        assert_eq!(*holidays.get(8).unwrap(), offset.ymd(2020, 10, 12));
-        assert_eq!(*holidays.get(9).unwrap(), offset.ymd(2020, 11, 2));
-        assert_eq!(*holidays.get(10).unwrap(), offset.ymd(2020, 11, 15));
-        assert_eq!(*holidays.get(11).unwrap(), offset.ymd(2020, 12, 25));
+        assert_eq!(holidays.first().unwrap().date, offset.ymd(2020, 1, 1));
+        assert_eq!(holidays.get(1).unwrap().date, offset.ymd(2020, 2, 24));
+        assert_eq!(holidays.get(2).unwrap().date, offset.ymd(2020, 2, 25));
+        assert_eq!(holidays.get(3).unwrap().date, offset.ymd(2020, 4, 10));
+        assert_eq!(holidays.get(4).unwrap().date, offset.ymd(2020, 4, 21));
+        assert_eq!(holidays.get(5).unwrap().date, offset.ymd(2020, 5, 1));
+        assert_eq!(holidays.get(6).unwrap().date, offset.ymd(2020, 6, 11));
+        assert_eq!(holidays.get(7).unwrap().date, offset.ymd(2020, 9, 7));
+        assert_eq!(holidays.get(8).unwrap().date, offset.ymd(2020, 10, 12));
+        assert_eq!(holidays.get(9).unwrap().date, offset.ymd(2020, 11, 2));
+        assert_eq!(holidays.get(10).unwrap().date, offset.ymd(2020, 11, 15));
+        assert_eq!(holidays.get(11).unwrap().date, offset.ymd(2020, 12, 25));
+    }
+
+    #[tokio::test]
+    async fn should_report_every_problem_in_strict_mode() {
+        let offset = FixedOffset::west(3 * 3600);
+
+        let result = load(
+            offset,
+            "tests_resources/problem_holidays.csv",
+            Observance::None,
+            LoadMode::Strict,
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid holidays file tests_resources/problem_holidays.csv. Invalid date format at line 4. Duplicate date 2022-04-21 at line 5. Invalid row at line 6. Date 2022-02-28 out of order at line 7."
+        );
+
+        let result = load(
+            offset,
+            "tests_resources/empty_holidays.csv",
+            Observance::None,
+            LoadMode::Strict,
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid holidays file tests_resources/empty_holidays.csv. No holidays in file tests_resources/empty_holidays.csv."
+        );
+    }
+
+    #[tokio::test]
+    async fn should_skip_problems_in_lenient_mode() {
+        let offset = FixedOffset::west(3 * 3600);
+
+        let result = load(
+            offset,
+            "tests_resources/problem_holidays.csv",
+            Observance::None,
+            LoadMode::Lenient,
+        );
+        let dates: Vec<_> = result.unwrap().iter().map(|holiday| holiday.date).collect();
+        assert_eq!(
+            dates,
+            vec![
+                offset.ymd(2022, 1, 1),
+                offset.ymd(2022, 4, 21),
+                offset.ymd(2022, 2, 28)
+            ]
+        );
+
+        let result = load(
+            offset,
+            "tests_resources/empty_holidays.csv",
+            Observance::None,
+            LoadMode::Lenient,
+        );
+        assert!(result.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_load_ics_events() {
+        let offset = FixedOffset::west(3 * 3600);
+
+        let result = load_with_format(
+            HolidaysFormat::Ics,
+            offset,
+            "tests_resources/holidays.ics",
+            Observance::NextMonday,
+            LoadMode::Strict,
+        );
+        let LoadedHolidays { holidays, rules } = result.unwrap();
+        let dates: Vec<_> = holidays.iter().map(|holiday| holiday.date).collect();
+        assert_eq!(
+            dates,
+            vec![
+                offset.ymd(2022, 1, 1),
+                offset.ymd(2022, 2, 28),
+                offset.ymd(2022, 3, 1),
+                offset.ymd(2022, 12, 25),
+                offset.ymd(2023, 1, 1)
+            ]
+        );
+        assert_eq!(holidays[2].name.as_deref(), Some("Carnaval"));
+        assert_eq!(holidays[2].observance, Observance::NextMonday);
+        assert_eq!(holidays[3].name.as_deref(), Some("Office closed"));
+
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].rule, HolidayRule::Fixed { month: 12, day: 25 });
+        assert_eq!(rules[0].name.as_deref(), Some("Natal"));
+        assert_eq!(rules[0].observance, Observance::NextMonday);
+        let christmas: Vec<_> = expand(&rules, offset, 2021, 2031)
+            .into_iter()
+            .map(|holiday| holiday.date)
+            .collect();
+        assert_eq!(christmas.len(), 10);
+        assert_eq!(christmas[0], offset.ymd(2022, 12, 25));
+        assert_eq!(christmas[9], offset.ymd(2031, 12, 25));
+
+        let result = load_with_format(
+            HolidaysFormat::Ics,
+            offset,
+            "tests_resources/invalid_holidays.ics",
+            Observance::None,
+            LoadMode::Strict,
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid holidays file tests_resources/invalid_holidays.ics. Invalid event at line 3. No holidays in file tests_resources/invalid_holidays.ics."
+        );
+
+        assert_eq!(HolidaysFormat::of("holidays.ICS"), HolidaysFormat::Ics);
+        assert_eq!(HolidaysFormat::of("holidays.csv"), HolidaysFormat::Csv);
+        assert_eq!(
+            "ics".parse::<HolidaysFormat>().unwrap(),
+            HolidaysFormat::Ics
+        );
     }
 }
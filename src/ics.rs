@@ -0,0 +1,492 @@
+use chrono::{Datelike, Duration, FixedOffset, NaiveDate, Weekday};
+use thiserror::Error;
+
+use crate::holiday::Observance;
+use crate::holiday_rules::{HolidayRule, RecurringHoliday};
+use crate::ics::IcsError::{InvalidDate, InvalidDuration, MissingStart, UnsupportedRecurrence};
+use crate::schedule;
+
+/// All-day `VEVENT` of an iCalendar file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub start: NaiveDate,
+    /// Number of days of each occurrence, `1` unless `DTEND` or `DURATION` say otherwise.
+    pub days: i64,
+    pub summary: Option<String>,
+    pub recurrence: Option<Recurrence>,
+    /// Occurrences removed by `EXDATE`.
+    pub exceptions: Vec<NaiveDate>,
+}
+
+/// Yearly `RRULE`, on a fixed date or an nth weekday of a month.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recurrence {
+    pub rule: HolidayRule,
+    pub interval: u32,
+    pub count: Option<u32>,
+    pub until: Option<NaiveDate>,
+}
+
+/// Recurrences stop once this many of their years in a row have no occurrence, like a
+/// `BYMONTHDAY=29` of February with an `INTERVAL` skipping every leap year.
+const MAX_YEARS_WITHOUT_OCCURRENCE: u32 = 8;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum IcsError {
+    #[error("Invalid date `{0}`.")]
+    InvalidDate(String),
+    #[error("Missing DTSTART.")]
+    MissingStart,
+    #[error("Invalid duration `{0}`. Expected whole days, like `P1D`.")]
+    InvalidDuration(String),
+    #[error("Unsupported recurrence rule `{0}`. Expected a yearly rule on a date or an nth weekday, like `FREQ=YEARLY;BYMONTH=11;BYDAY=4TH`.")]
+    UnsupportedRecurrence(String),
+}
+
+/// Properties of a `VEVENT` read so far.
+#[derive(Debug, Default)]
+struct EventProperties {
+    start: Option<Property>,
+    end: Option<Property>,
+    duration: Option<String>,
+    rule: Option<String>,
+    exceptions: Vec<Property>,
+    summary: Option<String>,
+    cancelled: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Property {
+    value: String,
+    is_date: bool,
+}
+
+impl Event {
+    /// Dates of every occurrence of the event, each day of multi-day events included. Events
+    /// recurring without `COUNT` or `UNTIL` have none, being holiday rules instead.
+    pub fn dates(&self) -> Vec<NaiveDate> {
+        let starts = match &self.recurrence {
+            None => vec![self.start],
+            Some(recurrence) if recurrence.is_unbounded() => Vec::new(),
+            Some(recurrence) => recurrence.occurrences(self.start),
+        };
+
+        starts
+            .into_iter()
+            .filter(|start| !self.exceptions.contains(start))
+            .flat_map(|start| (0..self.days).map(move |day| start + Duration::days(day)))
+            .collect()
+    }
+
+    /// Holiday rule of events recurring without `COUNT` or `UNTIL`, named after their summary.
+    pub fn recurring_holiday(&self, observance: Observance) -> Option<RecurringHoliday> {
+        let recurrence = self
+            .recurrence
+            .as_ref()
+            .filter(|recurrence| recurrence.is_unbounded())?;
+
+        Some(RecurringHoliday {
+            since: Some(self.start),
+            interval: recurrence.interval,
+            days: self.days,
+            exceptions: self.exceptions.clone(),
+            ..RecurringHoliday::new(recurrence.rule.clone(), observance)
+                .with_name(self.summary.clone())
+        })
+    }
+}
+
+impl Recurrence {
+    fn is_unbounded(&self) -> bool {
+        self.count.is_none() && self.until.is_none()
+    }
+
+    fn occurrences(&self, start: NaiveDate) -> Vec<NaiveDate> {
+        let last_year = self.until.map_or(i32::MAX, |until| until.year());
+        let offset = FixedOffset::east(0);
+        let mut occurrences = Vec::new();
+        let mut year = start.year();
+        let mut years_without_occurrence = 0;
+
+        while year <= last_year
+            && years_without_occurrence < MAX_YEARS_WITHOUT_OCCURRENCE
+            && self
+                .count
+                .is_none_or(|count| occurrences.len() < count as usize)
+        {
+            match self.rule.date(offset, year).map(|date| date.naive_utc()) {
+                Some(date) if date >= start && self.until.is_none_or(|until| date <= until) => {
+                    occurrences.push(date);
+                    years_without_occurrence = 0;
+                }
+                _ => years_without_occurrence += 1,
+            }
+            year = match year.checked_add(self.interval as i32) {
+                Some(year) => year,
+                None => break,
+            };
+        }
+        occurrences
+    }
+}
+
+/// Reads the all-day events of an iCalendar file along with the line where each one begins.
+/// Timed and cancelled events are skipped.
+pub fn read_events(content: &str) -> Vec<(u64, Result<Event, IcsError>)> {
+    let mut events = Vec::new();
+    let mut current: Option<(u64, EventProperties)> = None;
+    let mut nested = 0;
+
+    for (line, content_line) in unfold(content) {
+        let (name, value) = match content_line.split_once(':') {
+            Some((name, value)) => (name, value),
+            None => continue,
+        };
+        let mut params = name.split(';');
+        let name = params.next().unwrap_or_default().to_uppercase();
+        let is_date = params.any(|param| param.eq_ignore_ascii_case("VALUE=DATE"));
+
+        match (name.as_str(), current.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VEVENT") => {
+                current = Some((line, EventProperties::default()));
+            }
+            ("BEGIN", Some(_)) => nested += 1,
+            ("END", Some(_)) if nested > 0 => nested -= 1,
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => {
+                if let Some((line, properties)) = current.take() {
+                    if let Some(event) = properties.into_event().transpose() {
+                        events.push((line, event));
+                    }
+                }
+            }
+            (_, Some((_, properties))) if nested == 0 => {
+                let property = Property {
+                    value: value.trim().to_string(),
+                    is_date,
+                };
+                match name.as_str() {
+                    "DTSTART" => properties.start = Some(property),
+                    "DTEND" => properties.end = Some(property),
+                    "DURATION" => properties.duration = Some(property.value),
+                    "RRULE" => properties.rule = Some(property.value),
+                    "EXDATE" => properties.exceptions.push(property),
+                    "SUMMARY" => properties.summary = Some(unescape(&property.value)),
+                    "STATUS" => properties.cancelled = property.value == "CANCELLED",
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    events
+}
+
+impl EventProperties {
+    /// The event, `None` when it is not an all-day event or was cancelled.
+    fn into_event(self) -> Result<Option<Event>, IcsError> {
+        let start = match &self.start {
+            None => return Err(MissingStart),
+            Some(start) => match read_date(start)? {
+                Some(start) if !self.cancelled => start,
+                _ => return Ok(None),
+            },
+        };
+
+        let days = match (&self.end, &self.duration) {
+            (Some(end), _) => match read_date(end)? {
+                Some(end) => (end - start).num_days().max(1),
+                None => return Ok(None),
+            },
+            (None, Some(duration)) => schedule::parse_duration(duration)
+                .filter(|duration| duration.num_days() > 0)
+                .filter(|duration| *duration == Duration::days(duration.num_days()))
+                .map(|duration| duration.num_days())
+                .ok_or_else(|| InvalidDuration(duration.clone()))?,
+            (None, None) => 1,
+        };
+
+        let recurrence = match &self.rule {
+            None => None,
+            Some(rule) => Some(read_recurrence(rule, start)?),
+        };
+
+        let mut exceptions = Vec::new();
+        for exception in &self.exceptions {
+            for value in exception.value.split(',') {
+                let property = Property {
+                    value: value.to_string(),
+                    is_date: exception.is_date,
+                };
+                if let Some(date) = read_date(&property)? {
+                    exceptions.push(date);
+                }
+            }
+        }
+
+        Ok(Some(Event {
+            start,
+            days,
+            summary: self.summary.filter(|summary| !summary.is_empty()),
+            recurrence,
+            exceptions,
+        }))
+    }
+}
+
+/// Date of a `VALUE=DATE` property, `None` for date-times.
+fn read_date(property: &Property) -> Result<Option<NaiveDate>, IcsError> {
+    let value = property.value.trim();
+    if !property.is_date && value.contains('T') {
+        return Ok(None);
+    }
+    NaiveDate::parse_from_str(value, "%Y%m%d")
+        .map(Some)
+        .map_err(|_| InvalidDate(value.to_string()))
+}
+
+fn read_recurrence(value: &str, start: NaiveDate) -> Result<Recurrence, IcsError> {
+    let unsupported = || UnsupportedRecurrence(value.to_string());
+    let mut frequency = None;
+    let mut recurrence = Recurrence {
+        rule: HolidayRule::Fixed {
+            month: start.month(),
+            day: start.day(),
+        },
+        interval: 1,
+        count: None,
+        until: None,
+    };
+    let mut month = start.month();
+    let mut month_day = start.day();
+    let mut weekday = None;
+
+    for part in value.split(';') {
+        let (key, value) = part.split_once('=').ok_or_else(unsupported)?;
+        match key.to_uppercase().as_str() {
+            "FREQ" => frequency = Some(value.to_uppercase()),
+            "INTERVAL" => {
+                recurrence.interval = value
+                    .parse()
+                    .ok()
+                    .filter(|interval| *interval > 0)
+                    .ok_or_else(unsupported)?
+            }
+            "COUNT" => recurrence.count = Some(value.parse().map_err(|_| unsupported())?),
+            "UNTIL" => {
+                let until = value.get(..8).ok_or_else(unsupported)?;
+                recurrence.until =
+                    Some(NaiveDate::parse_from_str(until, "%Y%m%d").map_err(|_| unsupported())?);
+            }
+            "BYMONTH" => month = value.parse().map_err(|_| unsupported())?,
+            "BYMONTHDAY" => month_day = value.parse().map_err(|_| unsupported())?,
+            "BYDAY" => weekday = Some(read_nth_weekday(value).ok_or_else(unsupported)?),
+            "WKST" => {}
+            _ => return Err(unsupported()),
+        }
+    }
+
+    if frequency.as_deref() != Some("YEARLY") || !(1..=12).contains(&month) {
+        return Err(unsupported());
+    }
+    recurrence.rule = match weekday {
+        Some((weekday, n)) => HolidayRule::NthWeekday { month, weekday, n },
+        None => {
+            NaiveDate::from_ymd_opt(2000, month, month_day).ok_or_else(unsupported)?;
+            HolidayRule::Fixed {
+                month,
+                day: month_day,
+            }
+        }
+    };
+    Ok(recurrence)
+}
+
+/// Reads a `BYDAY` value like `4TH` or `-1MO`. Weekdays without an ordinal are not holidays.
+fn read_nth_weekday(value: &str) -> Option<(Weekday, i8)> {
+    let split = value.len().checked_sub(2)?;
+    let (n, weekday) = value.split_at(split);
+    let weekday = match weekday.to_uppercase().as_str() {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    };
+    let n = n.trim_start_matches('+').parse::<i8>().ok()?;
+    Some((weekday, n)).filter(|(_, n)| *n != 0 && (-5..=5).contains(n))
+}
+
+/// Content lines joined with their folded continuations, along with the line they start.
+fn unfold(content: &str) -> Vec<(u64, String)> {
+    let mut lines: Vec<(u64, String)> = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        match lines.last_mut() {
+            Some((_, last)) if line.starts_with(' ') || line.starts_with('\t') => {
+                last.push_str(&line[1..])
+            }
+            _ => lines.push((index as u64 + 1, line.to_string())),
+        }
+    }
+    lines
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(char) = chars.next() {
+        match char {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => unescaped.push(' '),
+                Some(escaped) => unescaped.push(escaped),
+                None => {}
+            },
+            _ => unescaped.push(char),
+        }
+    }
+    unescaped.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_read_all_day_events() {
+        let content = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART;VALUE=DATE:20221225\r\n\
+            SUMMARY:Christmas\\, office closed\r\n\
+            RRULE:FREQ=YEARLY;COUNT=3\r\n\
+            BEGIN:VALARM\r\n\
+            DTSTART;VALUE=DATE:20221224\r\n\
+            END:VALARM\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART:20221110T090000Z\r\n\
+            SUMMARY:Meeting\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART;VALUE=DATE:20221229\r\n\
+            DTEND;VALUE=DATE:20230101\r\n\
+            SUMMARY:Year end\r\n\
+            \x20 break\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            SUMMARY:Undated\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+
+        let events = read_events(content);
+        assert_eq!(events.len(), 3);
+
+        let (line, christmas) = &events[0];
+        let christmas = christmas.as_ref().unwrap();
+        assert_eq!(*line, 2);
+        assert_eq!(
+            christmas.summary.as_deref(),
+            Some("Christmas, office closed")
+        );
+        assert_eq!(
+            christmas.dates(),
+            vec![
+                NaiveDate::from_ymd(2022, 12, 25),
+                NaiveDate::from_ymd(2023, 12, 25),
+                NaiveDate::from_ymd(2024, 12, 25)
+            ]
+        );
+
+        let year_end = events[1].1.as_ref().unwrap();
+        assert_eq!(year_end.summary.as_deref(), Some("Year end break"));
+        assert_eq!(year_end.dates().len(), 3);
+
+        assert_eq!(events[2], (20, Err(MissingStart)));
+    }
+
+    #[test]
+    fn should_expand_yearly_recurrences() {
+        let start = NaiveDate::from_ymd(2020, 11, 26);
+        let recurrence =
+            read_recurrence("FREQ=YEARLY;BYMONTH=11;BYDAY=4TH;COUNT=3", start).unwrap();
+        assert_eq!(
+            recurrence.occurrences(start),
+            vec![
+                start,
+                NaiveDate::from_ymd(2021, 11, 25),
+                NaiveDate::from_ymd(2022, 11, 24)
+            ]
+        );
+
+        let start = NaiveDate::from_ymd(2020, 5, 25);
+        let recurrence = read_recurrence(
+            "FREQ=YEARLY;INTERVAL=2;BYDAY=-1MO;BYMONTH=5;UNTIL=20241231",
+            start,
+        )
+        .unwrap();
+        assert_eq!(
+            recurrence.occurrences(start),
+            vec![
+                start,
+                NaiveDate::from_ymd(2022, 5, 30),
+                NaiveDate::from_ymd(2024, 5, 27)
+            ]
+        );
+
+        let thanksgiving_start = NaiveDate::from_ymd(2020, 11, 26);
+        let thanksgiving = Event {
+            start: thanksgiving_start,
+            days: 2,
+            summary: Some("Thanksgiving".to_string()),
+            recurrence: Some(read_recurrence("FREQ=YEARLY;BYDAY=4TH", thanksgiving_start).unwrap()),
+            exceptions: Vec::new(),
+        };
+        assert!(thanksgiving.dates().is_empty());
+        let rule = thanksgiving
+            .recurring_holiday(Observance::NextMonday)
+            .unwrap();
+        assert_eq!(
+            rule.rule,
+            HolidayRule::NthWeekday {
+                month: 11,
+                weekday: Weekday::Thu,
+                n: 4
+            }
+        );
+        assert_eq!(rule.since, Some(thanksgiving_start));
+        assert_eq!(rule.days, 2);
+        assert_eq!(rule.observance, Observance::NextMonday);
+        assert_eq!(rule.name.as_deref(), Some("Thanksgiving"));
+
+        assert_eq!(
+            read_recurrence("FREQ=WEEKLY;BYDAY=MO", start)
+                .unwrap_err()
+                .to_string(),
+            "Unsupported recurrence rule `FREQ=WEEKLY;BYDAY=MO`. Expected a yearly rule on a date or an nth weekday, like `FREQ=YEARLY;BYMONTH=11;BYDAY=4TH`."
+        );
+        assert!(read_recurrence("FREQ=YEARLY;BYDAY=MO", start).is_err());
+        assert!(read_recurrence("FREQ=YEARLY;BYMONTH=13;BYDAY=1MO", start).is_err());
+        assert!(read_recurrence("FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=30", start).is_err());
+        assert!(read_recurrence("FREQ=YEARLY;BYMONTHDAY=32", start).is_err());
+    }
+
+    #[test]
+    fn should_stop_recurrences_without_occurrences() {
+        let start = NaiveDate::from_ymd(2024, 2, 29);
+        let recurrence = read_recurrence("FREQ=YEARLY;COUNT=3", start).unwrap();
+        assert_eq!(
+            recurrence.occurrences(start),
+            vec![
+                start,
+                NaiveDate::from_ymd(2028, 2, 29),
+                NaiveDate::from_ymd(2032, 2, 29)
+            ]
+        );
+
+        let start = NaiveDate::from_ymd(2016, 2, 29);
+        let recurrence = read_recurrence("FREQ=YEARLY;BYMONTH=2;BYDAY=5MO;COUNT=3", start).unwrap();
+        assert_eq!(recurrence.occurrences(start), vec![start]);
+    }
+}
@@ -0,0 +1,14 @@
+pub mod calendar_handler;
+pub mod calendars;
+pub mod handler;
+pub mod holiday;
+pub mod holiday_rules;
+pub mod holidays_loader;
+pub mod ics;
+pub mod profiles;
+pub mod reload;
+pub mod schedule;
+pub mod scheduler;
+pub mod time_zone;
+pub mod validation;
+pub mod working_days;
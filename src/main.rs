@@ -1,75 +1,249 @@
+use std::process::ExitCode;
 use std::sync::Arc;
 
-use chrono::FixedOffset;
-use clap::Parser;
+use chrono::{FixedOffset, Utc};
+use clap::{Args, Parser, Subcommand};
 use tonic::transport::Server;
-use tracing::info;
+use tracing::{error, info};
 
-use crate::handler::external_scaler_server::ExternalScalerServer;
-use crate::handler::GrpcHandler;
-use crate::working_days::WorkingDays;
-
-mod handler;
-mod holidays_loader;
-mod working_days;
+use working_days_scaler::calendar_handler::calendar_server::CalendarServer;
+use working_days_scaler::calendar_handler::CalendarHandler;
+use working_days_scaler::calendars::CalendarSettings;
+use working_days_scaler::handler::external_scaler_server::ExternalScalerServer;
+use working_days_scaler::handler::GrpcHandler;
+use working_days_scaler::holiday::Observance;
+use working_days_scaler::holidays_loader::{HolidaysFormat, LoadMode};
+use working_days_scaler::profiles::Profiles;
+use working_days_scaler::reload;
+use working_days_scaler::reload::CalendarsSource;
+use working_days_scaler::time_zone::CalendarTimeZone;
+use working_days_scaler::validation;
+use working_days_scaler::validation::ValidationSettings;
+use working_days_scaler::working_days::Weekend;
 
 #[derive(Parser, Debug)]
 #[command(name = "working-days-scaler")]
 #[command(author = "José V. Almada")]
 #[command(version = "1.0")]
 #[command(about = "External scaler for KEDA", long_about = None)]
-pub struct Args {
-    /// Path to the holidays CSV.
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    serve: ServeArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Runs the gRPC server. The default.
+    Serve(ServeArgs),
+    /// Checks holidays files before deploying them, exiting non-zero on problems.
+    Validate(ValidateArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Path to the holidays CSV or iCalendar file.
     #[arg(short = 'f', long, default_value_t = String::from("holidays.csv"))]
     holidays_file: String,
+    /// Format of the holidays files, `csv` or `ics`. Given by the file extension by default.
+    #[arg(long)]
+    holidays_format: Option<HolidaysFormat>,
+    /// Path to a TOML file with named calendars. Replaces the holidays CSV when present.
+    #[arg(short = 'c', long)]
+    calendars_file: Option<String>,
+    /// Path to a CSV with recurring holiday rules, expanded for any year needed.
+    #[arg(short = 'r', long)]
+    rules_file: Option<String>,
+    /// Path to a TOML file with named replica profiles, referenced by the `profile` metadata.
+    #[arg(short = 'P', long)]
+    profiles_file: Option<String>,
     /// The port that the gRPC server will be listening.
     #[arg(short = 'p', long, default_value_t = 8080)]
     port: u16,
-    /// The time offset in seconds. Value between -86400 and -86400.
-    #[arg(short = 't', long, allow_negative_numbers = true, default_value_t = 0)]
+    /// The time offset in seconds. Value between -86399 and 86399. Ignored when a time zone is set.
+    #[arg(
+        short = 't',
+        long,
+        allow_negative_numbers = true,
+        default_value_t = 0,
+        value_parser = clap::value_parser!(i32).range(-86399..=86399)
+    )]
     time_offset: i32,
-    /// The interval in seconds between IsActiveStream messages stream.
+    /// IANA time zone, like `America/New_York`. Follows daylight saving time transitions.
+    #[arg(short = 'z', long)]
+    time_zone: Option<CalendarTimeZone>,
+    /// The weekdays that are not working days. Comma separated list or `none`.
+    #[arg(short = 'w', long, default_value_t = Weekend::default())]
+    weekend: Weekend,
+    /// How holidays on a weekend are observed: `none`, `nearest_weekday`, `next_monday` or `previous_friday`.
+    #[arg(short = 'o', long, default_value_t = Observance::None)]
+    observance: Observance,
+    /// Fails on any problem of the holidays and rules files, reporting all of them.
+    #[arg(long, conflicts_with = "lenient")]
+    strict: bool,
+    /// Skips invalid rows and duplicate dates of the holidays and rules files, logging them. The default.
+    #[arg(long)]
+    lenient: bool,
+    /// The interval in seconds between heartbeat IsActiveStream messages, repeating the current state. `0` disables them.
     #[arg(short = 'i', long, default_value_t = 60)]
     push_interval: u64,
 }
 
+#[derive(Args, Debug)]
+pub struct ValidateArgs {
+    /// Paths to the holidays CSV or iCalendar files.
+    #[arg(required = true)]
+    holidays_files: Vec<String>,
+    /// Format of the holidays files, `csv` or `ics`. Given by the file extension by default.
+    #[arg(long)]
+    holidays_format: Option<HolidaysFormat>,
+    /// The weekdays that are not working days. Comma separated list or `none`.
+    #[arg(short = 'w', long, default_value_t = Weekend::default())]
+    weekend: Weekend,
+    /// How holidays on a weekend are observed: `none`, `nearest_weekday`, `next_monday` or `previous_friday`.
+    #[arg(short = 'o', long, default_value_t = Observance::None)]
+    observance: Observance,
+    /// Reports holidays on a weekend without an observance.
+    #[arg(long)]
+    require_observance: bool,
+    /// Years with fewer holidays are reported.
+    #[arg(long, default_value_t = 5)]
+    min_holidays: usize,
+    /// Reports files whose working days end within this many days from today.
+    #[arg(long, default_value_t = 365)]
+    horizon_days: u32,
+}
+
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> Result<ExitCode, Box<dyn std::error::Error>> {
     tracing_subscriber::fmt::init();
 
-    let args = Args::parse();
+    let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Validate(args)) => Ok(validate(args)),
+        Some(Command::Serve(args)) => serve(args).await.map(|_| ExitCode::SUCCESS),
+        None => serve(cli.serve).await.map(|_| ExitCode::SUCCESS),
+    }
+}
 
-    let time_offset = FixedOffset::east(args.time_offset);
-    info!("Using configured time offset {}.", time_offset);
+fn validate(args: ValidateArgs) -> ExitCode {
+    let settings = ValidationSettings {
+        weekend: args.weekend,
+        observance: args.observance,
+        holidays_format: args.holidays_format,
+        require_observance: args.require_observance,
+        min_holidays: args.min_holidays,
+        horizon_days: args.horizon_days,
+    };
+    let today = Utc::now().date_naive();
 
-    let holidays = holidays_loader::load(time_offset, &args.holidays_file)?;
-    info!(
-        "Loaded {} holidays from {}.",
-        holidays.len(),
-        args.holidays_file
-    );
+    let mut valid = true;
+    for holidays_file in &args.holidays_files {
+        let problems = validation::validate(holidays_file, &settings, today);
+        if problems.is_empty() {
+            println!("{}: OK", holidays_file);
+        }
+        for problem in &problems {
+            println!("{}: {}", holidays_file, problem);
+        }
+        valid &= problems.is_empty();
+    }
 
-    let working_days = WorkingDays::build(time_offset, holidays)?;
-    info!(
-        "Application ready to calculate working days MTD between {} and {}.",
-        working_days.start_date, working_days.end_date
-    );
+    if valid {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+async fn serve(args: ServeArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let time_zone = args
+        .time_zone
+        .unwrap_or_else(|| FixedOffset::east(args.time_offset).into());
+    info!("Using configured time zone {}.", time_zone);
+    info!("Using configured weekend {}.", args.weekend);
+    info!("Using configured observance {}.", args.observance);
+
+    let settings = CalendarSettings {
+        time_zone,
+        weekend: args.weekend,
+        observance: args.observance,
+        load_mode: if args.strict {
+            LoadMode::Strict
+        } else {
+            LoadMode::Lenient
+        },
+        holidays_format: args.holidays_format,
+    };
+
+    let source = match &args.calendars_file {
+        Some(calendars_file) => CalendarsSource::CalendarsFile(calendars_file.clone()),
+        None => CalendarsSource::HolidaysFile {
+            holidays_file: args.holidays_file.clone(),
+            rules_file: args.rules_file.clone(),
+        },
+    };
+    let calendars = source.load(settings)?;
+    match &source {
+        CalendarsSource::CalendarsFile(calendars_file) => info!(
+            "Loaded {} calendars from {}. Default calendar: {}.",
+            calendars.len(),
+            calendars_file,
+            calendars.default_name().unwrap_or("none")
+        ),
+        CalendarsSource::HolidaysFile { .. } => {
+            let working_days = calendars.get(None)?;
+            info!(
+                "Application ready to calculate working days MTD between {} and {}.",
+                working_days.start_date, working_days.end_date
+            );
+        }
+    }
+    let calendars = Arc::new(calendars);
+
+    let watched = Arc::clone(&calendars);
+    tokio::spawn(async move {
+        if let Err(err) = reload::watch(watched, source, settings).await {
+            error!("Calendars will not be reloaded. {}", err);
+        }
+    });
+
+    let profiles = match &args.profiles_file {
+        Some(profiles_file) => {
+            let profiles = Profiles::load(profiles_file)?;
+            info!(
+                "Loaded {} replica profiles from {}.",
+                profiles.len(),
+                profiles_file
+            );
+            profiles
+        }
+        None => Profiles::default(),
+    };
 
     info!(
-        "Interval between IsActiveStream messages stream: {} s.",
+        "Interval between IsActiveStream heartbeat messages: {} s.",
         args.push_interval
     );
 
     let addr = format!("[::1]:{}", args.port).parse().unwrap();
     info!("GRPC server listening on {}.", addr);
 
-    let handler = GrpcHandler {
-        working_days: Arc::new(working_days),
-        push_interval: args.push_interval,
+    let handler = GrpcHandler::new(
+        Arc::clone(&calendars),
+        Arc::new(profiles),
+        args.push_interval,
+    );
+    let calendar_handler = CalendarHandler {
+        calendars,
+        scheduler: handler.scheduler(),
     };
 
     Server::builder()
         .add_service(ExternalScalerServer::new(handler))
+        .add_service(CalendarServer::new(calendar_handler))
         .serve(addr)
         .await?;
 
@@ -0,0 +1,226 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+use chrono::{Date, FixedOffset, NaiveTime};
+use csv::StringRecord;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::profiles::ProfilesError::{
+    ErrorOpeningFile, ErrorReadingConfig, InvalidConfig, InvalidReplicas, InvalidWindow,
+    InvalidWorkingDay, UnknownProfile,
+};
+use crate::schedule::{NthWorkingDays, ScheduleError, Window};
+use crate::working_days::{WorkingDays, WorkingDaysError};
+
+/// Named replica profiles referenced by the `profile` metadata.
+#[derive(Debug, Default)]
+pub struct Profiles {
+    profiles: HashMap<String, Profile>,
+}
+
+/// Replicas by working day and time of the day. The first matching cell wins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile(Vec<ProfileCell>);
+
+/// Replicas for the working days, inside the window when there is one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileCell {
+    pub working_days: NthWorkingDays,
+    pub window: Option<Window>,
+    pub replicas: u32,
+}
+
+#[derive(Error, Debug)]
+pub enum ProfilesError {
+    #[error("Error reading profiles file {0}.")]
+    ErrorReadingConfig(String, #[source] std::io::Error),
+    #[error("Invalid profiles file {0}.")]
+    InvalidConfig(String, #[source] toml::de::Error),
+    #[error("Error opening file {0}.")]
+    ErrorOpeningFile(String, #[source] csv::Error),
+    #[error("Invalid working day at line {1} of profile {0}.")]
+    InvalidWorkingDay(String, u64, #[source] ScheduleError),
+    #[error("Invalid window at line {1} of profile {0}.")]
+    InvalidWindow(String, u64, #[source] ScheduleError),
+    #[error("Invalid replicas at line {1} of profile {0}.")]
+    InvalidReplicas(String, u64),
+    #[error("Unknown profile `{0}`.")]
+    UnknownProfile(String),
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ProfilesConfig {
+    profiles: BTreeMap<String, String>,
+}
+
+impl Profiles {
+    pub fn new(profiles: HashMap<String, Profile>) -> Self {
+        Profiles { profiles }
+    }
+
+    /// Loads every profile CSV listed in a TOML profiles file.
+    pub fn load(profiles_file: &str) -> Result<Self, ProfilesError> {
+        let content = fs::read_to_string(profiles_file)
+            .map_err(|err| ErrorReadingConfig(profiles_file.to_string(), err))?;
+        let config: ProfilesConfig = toml::from_str(&content)
+            .map_err(|err| InvalidConfig(profiles_file.to_string(), err))?;
+
+        let mut profiles = HashMap::new();
+        for (name, profile_file) in config.profiles {
+            profiles.insert(name, load_profile(&profile_file)?);
+        }
+
+        Ok(Self::new(profiles))
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Profile, ProfilesError> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| UnknownProfile(name.to_string()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.profiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty()
+    }
+}
+
+impl Profile {
+    pub fn new(cells: Vec<ProfileCell>) -> Self {
+        Profile(cells)
+    }
+
+    /// Windows of the cells that have one.
+    pub fn windows(&self) -> impl Iterator<Item = &Window> {
+        self.0.iter().filter_map(|cell| cell.window.as_ref())
+    }
+
+    /// Replicas of the first cell matching the date and time, `None` when no cell matches.
+    /// Windows wrapping past midnight belong to the working day they started.
+    pub fn replicas(
+        &self,
+        working_days: &WorkingDays,
+        today: Date<FixedOffset>,
+        time: NaiveTime,
+    ) -> Result<Option<u32>, WorkingDaysError> {
+        for cell in &self.0 {
+            let day = match cell.window {
+                None => Some(today),
+                Some(window) => window.day(today, time),
+            };
+            if let Some(day) = day {
+                if cell.working_days.matches(working_days, day)? {
+                    return Ok(Some(cell.replicas));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Loads a profile CSV with the columns `working_day`, like `2-3`, `window`, like
+/// `06:00-18:00` or empty for the whole day, and `replicas`.
+pub fn load_profile(profile_file: &str) -> Result<Profile, ProfilesError> {
+    let mut cells = Vec::new();
+
+    let mut reader = csv::Reader::from_path(profile_file)
+        .map_err(|err| ErrorOpeningFile(profile_file.to_string(), err))?;
+
+    for result in reader.records() {
+        let record = result.map_err(|err| ErrorOpeningFile(profile_file.to_string(), err))?;
+        let line = line_number(&record);
+        let field = |index| record.get(index).map(str::trim).unwrap_or_default();
+
+        let working_days = field(0)
+            .parse::<NthWorkingDays>()
+            .map_err(|err| InvalidWorkingDay(profile_file.to_string(), line, err))?;
+        let window = match field(1) {
+            "" => None,
+            value => Some(
+                value
+                    .parse::<Window>()
+                    .map_err(|err| InvalidWindow(profile_file.to_string(), line, err))?,
+            ),
+        };
+        let replicas = field(2)
+            .parse::<u32>()
+            .map_err(|_| InvalidReplicas(profile_file.to_string(), line))?;
+
+        cells.push(ProfileCell {
+            working_days,
+            window,
+            replicas,
+        });
+    }
+
+    Ok(Profile::new(cells))
+}
+
+fn line_number(record: &StringRecord) -> u64 {
+    match record.position() {
+        None => 0,
+        Some(pos) => pos.line(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use crate::working_days::Weekend;
+
+    use super::*;
+
+    #[test]
+    fn should_load_profiles() {
+        let result = Profiles::load("tests_resources/profiles.toml");
+        assert!(result.is_ok());
+
+        let profiles = result.unwrap();
+        assert_eq!(profiles.len(), 1);
+        assert!(profiles.get("month_end").is_ok());
+        assert_eq!(
+            profiles.get("payroll").unwrap_err().to_string(),
+            "Unknown profile `payroll`."
+        );
+
+        let result = load_profile("tests_resources/invalid_profile.csv");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid replicas at line 3 of profile tests_resources/invalid_profile.csv."
+        );
+    }
+
+    #[test]
+    fn should_return_replicas_of_matching_cell() {
+        let offset = FixedOffset::west(3 * 3600);
+        let working_days = WorkingDays::build(
+            offset,
+            Weekend::default(),
+            vec![offset.ymd(2022, 11, 2).into()],
+        )
+        .unwrap();
+        let profile = load_profile("tests_resources/month_end_profile.csv").unwrap();
+        let replicas = |day, hour| {
+            profile
+                .replicas(
+                    &working_days,
+                    offset.ymd(2022, 11, day),
+                    NaiveTime::from_hms(hour, 0, 0),
+                )
+                .unwrap()
+        };
+
+        // 1st is the first working day, 3rd and 4th the second and third, 30th the last one
+        assert_eq!(replicas(1, 12), Some(40));
+        assert_eq!(replicas(3, 12), Some(20));
+        assert_eq!(replicas(4, 20), Some(5));
+        assert_eq!(replicas(7, 12), None);
+        assert_eq!(replicas(30, 3), Some(30));
+    }
+}
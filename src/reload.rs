@@ -0,0 +1,264 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use thiserror::Error;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+use crate::calendars;
+use crate::calendars::{CalendarSettings, Calendars, CalendarsError, DEFAULT_CALENDAR};
+#[cfg(unix)]
+use crate::reload::ReloadError::ErrorListeningSignal;
+use crate::reload::ReloadError::ErrorWatchingFiles;
+
+/// Wait for the remaining changes of a file update before reloading.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Files the calendars are loaded from, read again on reload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CalendarsSource {
+    HolidaysFile {
+        holidays_file: String,
+        rules_file: Option<String>,
+    },
+    CalendarsFile(String),
+}
+
+#[derive(Error, Debug)]
+pub enum ReloadError {
+    #[error("Error watching calendar files.")]
+    ErrorWatchingFiles(#[source] notify::Error),
+    #[cfg(unix)]
+    #[error("Error listening to SIGHUP.")]
+    ErrorListeningSignal(#[source] std::io::Error),
+}
+
+impl CalendarsSource {
+    pub fn load(&self, settings: CalendarSettings) -> Result<Calendars, CalendarsError> {
+        match self {
+            CalendarsSource::HolidaysFile {
+                holidays_file,
+                rules_file,
+            } => calendars::build_calendar(
+                DEFAULT_CALENDAR,
+                settings,
+                Some(holidays_file),
+                rules_file.as_deref(),
+            )
+            .map(Calendars::single),
+            CalendarsSource::CalendarsFile(calendars_file) => {
+                Calendars::load(settings, calendars_file)
+            }
+        }
+    }
+
+    /// Watched files. The holidays files of a calendars file are only read again on SIGHUP
+    /// or when the calendars file changes.
+    fn files(&self) -> Vec<&str> {
+        match self {
+            CalendarsSource::HolidaysFile {
+                holidays_file,
+                rules_file,
+            } => std::iter::once(holidays_file)
+                .chain(rules_file)
+                .map(String::as_str)
+                .collect(),
+            CalendarsSource::CalendarsFile(calendars_file) => vec![calendars_file],
+        }
+    }
+}
+
+/// Loads the calendars again and swaps their tables. A failure keeps the current tables.
+pub fn reload(calendars: &Calendars, source: &CalendarsSource, settings: CalendarSettings) -> bool {
+    match source.load(settings) {
+        Ok(reloaded) => {
+            calendars.swap(reloaded);
+            info!("Calendars reloaded.");
+            true
+        }
+        Err(err) => {
+            error!(
+                "Keeping the current calendars. Reload failed: {}",
+                error_chain(&err)
+            );
+            false
+        }
+    }
+}
+
+/// Reloads the calendars on SIGHUP, on Unix, and when their files change. Directories are watched, so
+/// files replaced by a Kubernetes ConfigMap update are also detected.
+pub async fn watch(
+    calendars: Arc<Calendars>,
+    source: CalendarsSource,
+    settings: CalendarSettings,
+) -> Result<(), ReloadError> {
+    let (tx, mut rx) = mpsc::channel(1);
+    let names: HashSet<OsString> = source
+        .files()
+        .iter()
+        .filter_map(|file| Path::new(file).file_name())
+        .map(OsString::from)
+        .collect();
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        if let Ok(event) = event {
+            if is_update(&event, &names) {
+                let _ = tx.try_send(());
+            }
+        }
+    })
+    .map_err(ErrorWatchingFiles)?;
+    let directories: HashSet<PathBuf> = source.files().iter().map(|file| directory(file)).collect();
+    for directory in &directories {
+        watcher
+            .watch(directory, RecursiveMode::NonRecursive)
+            .map_err(ErrorWatchingFiles)?;
+        info!("Watching {} for calendar changes.", directory.display());
+    }
+
+    let mut hangup = Hangup::listen()?;
+    loop {
+        tokio::select! {
+            Some(()) = rx.recv() => {
+                tokio::time::sleep(DEBOUNCE).await;
+                while rx.try_recv().is_ok() {}
+                info!("Calendar files changed, reloading.");
+            }
+            Some(()) = hangup.recv() => info!("SIGHUP received, reloading calendars."),
+            else => return Ok(()),
+        }
+
+        // Loading reads and parses files, kept off the async workers
+        let calendars = Arc::clone(&calendars);
+        let source = source.clone();
+        if let Err(err) =
+            tokio::task::spawn_blocking(move || reload(&calendars, &source, settings)).await
+        {
+            error!("Keeping the current calendars. Reload failed: {}", err);
+        }
+    }
+}
+
+/// SIGHUP listener. Without Unix signals it never fires and only file changes reload.
+struct Hangup {
+    #[cfg(unix)]
+    signal: Signal,
+}
+
+impl Hangup {
+    fn listen() -> Result<Self, ReloadError> {
+        Ok(Hangup {
+            #[cfg(unix)]
+            signal: signal(SignalKind::hangup()).map_err(ErrorListeningSignal)?,
+        })
+    }
+
+    async fn recv(&mut self) -> Option<()> {
+        #[cfg(unix)]
+        return self.signal.recv().await;
+        #[cfg(not(unix))]
+        std::future::pending().await
+    }
+}
+
+/// Changes to the watched files, or to the `..data` link swapped by ConfigMap updates.
+fn is_update(event: &Event, names: &HashSet<OsString>) -> bool {
+    !matches!(event.kind, EventKind::Access(_))
+        && event.paths.iter().any(|path| {
+            path.file_name()
+                .map(|name| names.contains(name) || name == "..data")
+                .unwrap_or(false)
+        })
+}
+
+fn directory(file: &str) -> PathBuf {
+    match Path::new(file).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+fn error_chain(err: &dyn Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push(' ');
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use chrono::{FixedOffset, TimeZone};
+
+    use crate::holiday::Observance;
+    use crate::holidays_loader::LoadMode;
+    use crate::working_days::Weekend;
+
+    use super::*;
+
+    #[test]
+    fn should_keep_calendars_when_reload_fails() {
+        let offset = FixedOffset::west(3 * 3600);
+        let settings = CalendarSettings {
+            time_zone: offset.into(),
+            weekend: Weekend::default(),
+            observance: Observance::None,
+            load_mode: LoadMode::Strict,
+            holidays_format: None,
+        };
+        let holidays_file = std::env::temp_dir().join("should_keep_calendars_holidays.csv");
+        let source = CalendarsSource::HolidaysFile {
+            holidays_file: holidays_file.to_str().unwrap().to_string(),
+            rules_file: None,
+        };
+        let nth_working_day = |calendars: &Calendars| {
+            let working_days = calendars.get(None).unwrap();
+            working_days
+                .working_days_mtd(offset.ymd(2022, 11, 4))
+                .unwrap()
+        };
+
+        fs::write(&holidays_file, "date\n2022-01-01\n").unwrap();
+        let calendars = source.load(settings).unwrap();
+        assert_eq!(nth_working_day(&calendars), 4);
+
+        fs::write(&holidays_file, "date\n2022-01-01\n2022-11-02\n").unwrap();
+        assert!(reload(&calendars, &source, settings));
+        assert_eq!(nth_working_day(&calendars), 3);
+
+        fs::write(&holidays_file, "date\n2022-01-01\n2022-11-0\n").unwrap();
+        assert!(!reload(&calendars, &source, settings));
+        assert_eq!(nth_working_day(&calendars), 3);
+
+        fs::remove_file(&holidays_file).unwrap();
+    }
+
+    #[test]
+    fn should_detect_updates_of_watched_files() {
+        let names: HashSet<OsString> = [OsString::from("holidays.csv")].into_iter().collect();
+        let event = |kind, path: &str| Event::new(kind).add_path(PathBuf::from(path));
+
+        let modify = EventKind::Modify(notify::event::ModifyKind::Any);
+        let access = EventKind::Access(notify::event::AccessKind::Any);
+        assert!(is_update(&event(modify, "/config/holidays.csv"), &names));
+        assert!(is_update(&event(modify, "/config/..data"), &names));
+        assert!(!is_update(&event(modify, "/config/other.csv"), &names));
+        assert!(!is_update(&event(access, "/config/holidays.csv"), &names));
+
+        assert_eq!(directory("holidays.csv"), PathBuf::from("."));
+        assert_eq!(directory("/config/holidays.csv"), PathBuf::from("/config"));
+    }
+}
@@ -0,0 +1,413 @@
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use chrono::{Date, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime};
+use thiserror::Error;
+
+use crate::schedule::ScheduleError::{InvalidWindow, InvalidWorkingDay, InvalidWorkingDayRange};
+use crate::working_days::{WorkingDays, WorkingDaysError};
+
+/// Working days of the month when a trigger is active, written as a comma separated list of
+/// days and ranges, like `1-3,5,-1`. Negative values count from the end of the month.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NthWorkingDays(Vec<RangeInclusive<i8>>);
+
+/// Activation window, inclusive. Windows ending before or when they start wrap past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    pub from: NaiveTime,
+    pub to: NaiveTime,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    #[error("Invalid working day `{0}` in metadata `nthWorkingDay`. Expected values between -31 and 31, like `1-3,5,-1`.")]
+    InvalidWorkingDay(String),
+    #[error("Invalid range `{0}` in metadata `nthWorkingDay`. Ranges go from the lowest to the highest day, both positive or both negative.")]
+    InvalidWorkingDayRange(String),
+    #[error("Invalid window `{0}` in metadata `windows`. Expected times formatted as `%H:%M` or `%H:%M:%S`, like `06:00-09:00`.")]
+    InvalidWindow(String),
+}
+
+impl NthWorkingDays {
+    /// Whether the date is one of the working days. Non-working days share the count of the
+    /// working day before them, or after them for negative values.
+    pub fn matches(
+        &self,
+        working_days: &WorkingDays,
+        date: Date<FixedOffset>,
+    ) -> Result<bool, WorkingDaysError> {
+        for range in &self.0 {
+            let nth_working_day = if *range.start() < 0 {
+                -(working_days.working_days_until_eom(date)? as i8)
+            } else {
+                working_days.working_days_mtd(date)? as i8
+            };
+            if range.contains(&nth_working_day) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl FromStr for NthWorkingDays {
+    type Err = ScheduleError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .split(',')
+            .map(|item| parse_working_day_range(item.trim()))
+            .collect::<Result<Vec<_>, _>>()
+            .map(NthWorkingDays)
+    }
+}
+
+fn parse_working_day_range(item: &str) -> Result<RangeInclusive<i8>, ScheduleError> {
+    // The range separator is a `-` right after a digit, `-3--1` being a range of negative days
+    let separator = item
+        .char_indices()
+        .skip(1)
+        .find(|(index, c)| *c == '-' && item[..*index].ends_with(|c: char| c.is_ascii_digit()));
+
+    match separator {
+        None => {
+            let day = parse_working_day(item)?;
+            Ok(day..=day)
+        }
+        Some((index, _)) => {
+            let start = parse_working_day(&item[..index])?;
+            let end = parse_working_day(&item[index + 1..])?;
+            if start > end || (start < 0) != (end < 0) {
+                return Err(InvalidWorkingDayRange(item.to_string()));
+            }
+            Ok(start..=end)
+        }
+    }
+}
+
+fn parse_working_day(value: &str) -> Result<i8, ScheduleError> {
+    value
+        .trim()
+        .parse::<i8>()
+        .ok()
+        .filter(|day| (-31..=31).contains(day))
+        .ok_or_else(|| InvalidWorkingDay(value.trim().to_string()))
+}
+
+impl Window {
+    /// Day owning the time when inside the window. The part of a window after midnight belongs
+    /// to the day the window started, and equal times are a whole day from that time.
+    pub fn day(&self, today: Date<FixedOffset>, time: NaiveTime) -> Option<Date<FixedOffset>> {
+        self.days(today, time, Duration::zero(), Duration::zero())
+            .into_iter()
+            .next()
+    }
+
+    /// Days owning the time when inside the window opened `lead` earlier and closed `hold`
+    /// later, latest first. The extended window still belongs to the day it started.
+    pub fn days(
+        &self,
+        today: Date<FixedOffset>,
+        time: NaiveTime,
+        lead: Duration,
+        hold: Duration,
+    ) -> Vec<Date<FixedOffset>> {
+        let now = today.naive_local().and_time(time);
+        let length = self.length();
+
+        let first = today.checked_sub_signed(Duration::days(hold.num_days() + 2));
+        let last = today.checked_add_signed(Duration::days(lead.num_days() + 1));
+        let (first, mut day) = match (first, last) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Vec::new(),
+        };
+        let mut days = Vec::new();
+        while day >= first {
+            let start = day.naive_local().and_time(self.from);
+            let opens = start.checked_sub_signed(lead);
+            let closes = start
+                .checked_add_signed(length)
+                .and_then(|end| end.checked_add_signed(hold));
+            if let (Some(opens), Some(closes)) = (opens, closes) {
+                if opens <= now && now <= closes {
+                    days.push(day);
+                }
+            }
+            day = match day.checked_sub_signed(Duration::days(1)) {
+                Some(day) => day,
+                None => break,
+            };
+        }
+        days
+    }
+
+    /// Local times around today when the window, extended by `lead` and `hold`, opens or
+    /// closes. Windows are inclusive, so they close a second after their end.
+    pub fn boundaries(
+        &self,
+        today: NaiveDate,
+        lead: Duration,
+        hold: Duration,
+    ) -> Vec<NaiveDateTime> {
+        let length = self.length();
+        let first = today.checked_sub_signed(Duration::days(hold.num_days() + 2));
+        let last = today.checked_add_signed(Duration::days(lead.num_days() + 2));
+        let (mut day, last) = match (first, last) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Vec::new(),
+        };
+        let mut boundaries = Vec::new();
+        while day <= last {
+            let start = day.and_time(self.from);
+            boundaries.extend(start.checked_sub_signed(lead));
+            boundaries.extend(start.checked_add_signed(length + hold + Duration::seconds(1)));
+            day = match day.succ_opt() {
+                Some(day) => day,
+                None => break,
+            };
+        }
+        boundaries
+    }
+
+    fn length(&self) -> Duration {
+        if self.from < self.to {
+            self.to - self.from
+        } else {
+            self.to - self.from + Duration::days(1)
+        }
+    }
+}
+
+impl FromStr for Window {
+    type Err = ScheduleError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidWindow(value.trim().to_string());
+        let (from, to) = value.split_once('-').ok_or_else(invalid)?;
+
+        Ok(Window {
+            from: parse_time(from).ok_or_else(invalid)?,
+            to: parse_time(to).ok_or_else(invalid)?,
+        })
+    }
+}
+
+/// Parses a comma separated list of windows, like `06:00-09:00,18:00-20:00`.
+pub fn parse_windows(value: &str) -> Result<Vec<Window>, ScheduleError> {
+    value.split(',').map(str::parse::<Window>).collect()
+}
+
+/// Parses a duration written in ISO-8601, like `PT15M`, or in short form, like `15m` or `1h 30m`.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let duration = if value.starts_with('P') {
+        iso8601::duration(value).ok()?.into()
+    } else {
+        humantime::parse_duration(value).ok()?
+    };
+    Duration::from_std(duration).ok()
+}
+
+fn parse_time(value: &str) -> Option<NaiveTime> {
+    let value = value.trim();
+    NaiveTime::parse_from_str(value, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(value, "%H:%M"))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use crate::working_days::Weekend;
+
+    use super::*;
+
+    #[test]
+    fn should_parse_nth_working_days() {
+        assert_eq!(
+            "1-3, 5,-1".parse::<NthWorkingDays>(),
+            Ok(NthWorkingDays(vec![1..=3, 5..=5, -1..=-1]))
+        );
+        assert_eq!(
+            "-3--1".parse::<NthWorkingDays>(),
+            Ok(NthWorkingDays(vec![-3..=-1]))
+        );
+
+        for invalid in ["", "1,,2", "jose", "32", "-32", "1-"] {
+            assert!(matches!(
+                invalid.parse::<NthWorkingDays>(),
+                Err(InvalidWorkingDay(_))
+            ));
+        }
+        assert_eq!(
+            "3-1".parse::<NthWorkingDays>(),
+            Err(InvalidWorkingDayRange("3-1".to_string()))
+        );
+        assert_eq!(
+            "-1-3".parse::<NthWorkingDays>(),
+            Err(InvalidWorkingDayRange("-1-3".to_string()))
+        );
+        assert_eq!(
+            "32".parse::<NthWorkingDays>().unwrap_err().to_string(),
+            "Invalid working day `32` in metadata `nthWorkingDay`. Expected values between -31 and 31, like `1-3,5,-1`."
+        );
+    }
+
+    #[test]
+    fn should_match_nth_working_days() {
+        let offset = FixedOffset::west(3 * 3600);
+        let working_days = WorkingDays::build(
+            offset,
+            Weekend::default(),
+            vec![offset.ymd(2022, 11, 2).into()],
+        )
+        .unwrap();
+        let nth_working_days = "1-3,5,-1".parse::<NthWorkingDays>().unwrap();
+        let matches = |day| {
+            nth_working_days
+                .matches(&working_days, offset.ymd(2022, 11, day))
+                .unwrap()
+        };
+
+        // 1st, 3rd, 4th, 7th and 8th are the working days 1 to 5, the 30th is the last one
+        assert!(matches(1));
+        assert!(matches(4));
+        assert!(!matches(7));
+        assert!(matches(8));
+        assert!(!matches(29));
+        assert!(matches(30));
+    }
+
+    #[test]
+    fn should_parse_windows() {
+        let time = |h, m| NaiveTime::from_hms(h, m, 0);
+
+        assert_eq!(
+            parse_windows("06:00-09:00, 18:00:00-20:00"),
+            Ok(vec![
+                Window {
+                    from: time(6, 0),
+                    to: time(9, 0)
+                },
+                Window {
+                    from: time(18, 0),
+                    to: time(20, 0)
+                },
+            ])
+        );
+
+        assert_eq!(
+            parse_windows("06:00-09:00,18:00"),
+            Err(InvalidWindow("18:00".to_string()))
+        );
+        assert_eq!(
+            parse_windows("06:00-25:00"),
+            Err(InvalidWindow("06:00-25:00".to_string()))
+        );
+    }
+
+    #[test]
+    fn should_parse_durations() {
+        assert_eq!(parse_duration("15m"), Some(Duration::minutes(15)));
+        assert_eq!(parse_duration("1h 30m"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("PT15M"), Some(Duration::minutes(15)));
+        assert_eq!(parse_duration("PT1H30S"), Some(Duration::seconds(3630)));
+        assert_eq!(parse_duration("15"), None);
+        assert_eq!(parse_duration("-15m"), None);
+        assert_eq!(parse_duration("quarter"), None);
+    }
+
+    #[test]
+    fn should_stop_at_the_end_of_the_date_range() {
+        let offset = FixedOffset::east(0);
+        let window = "06:00-18:00".parse::<Window>().unwrap();
+        let lead = Duration::days(7);
+
+        let last = offset.from_utc_date(&NaiveDate::MAX);
+        assert_eq!(
+            window.days(last, NaiveTime::from_hms(12, 0, 0), lead, lead),
+            Vec::<Date<FixedOffset>>::new()
+        );
+        assert!(window.boundaries(NaiveDate::MAX, lead, lead).is_empty());
+        assert!(window.boundaries(NaiveDate::MIN, lead, lead).is_empty());
+    }
+
+    #[test]
+    fn should_extend_windows_with_lead_and_hold_times() {
+        let offset = FixedOffset::west(3 * 3600);
+        let today = offset.ymd(2022, 11, 3);
+        let yesterday = offset.ymd(2022, 11, 2);
+        let tomorrow = offset.ymd(2022, 11, 4);
+        let window = |value: &str| value.parse::<Window>().unwrap();
+        let time = |value: &str| parse_time(value).unwrap();
+        let lead = Duration::minutes(15);
+        let hold = Duration::minutes(30);
+
+        let day = window("06:00-18:00");
+        assert_eq!(day.days(today, time("05:45"), lead, hold), vec![today]);
+        assert_eq!(
+            day.days(today, time("05:44"), lead, hold),
+            Vec::<Date<FixedOffset>>::new()
+        );
+        assert_eq!(day.days(today, time("18:30"), lead, hold), vec![today]);
+        assert_eq!(
+            day.days(today, time("18:31"), lead, hold),
+            Vec::<Date<FixedOffset>>::new()
+        );
+
+        // The lead time of tomorrow's window starts before midnight
+        let early = window("00:05-02:00");
+        assert_eq!(early.days(today, time("23:50"), lead, hold), vec![tomorrow]);
+        assert_eq!(early.days(today, time("02:30"), lead, hold), vec![today]);
+
+        let night = window("22:00-02:00");
+        assert_eq!(
+            night.days(today, time("02:30"), lead, hold),
+            vec![yesterday]
+        );
+        assert_eq!(night.days(today, time("21:45"), lead, hold), vec![today]);
+
+        let whole_day = window("06:00-06:00");
+        assert_eq!(
+            whole_day.days(today, time("06:10"), lead, hold),
+            vec![today, yesterday]
+        );
+    }
+
+    #[test]
+    fn should_list_window_boundaries() {
+        let today = NaiveDate::from_ymd(2022, 11, 3);
+        let at = |day, h, m, s| NaiveDate::from_ymd(2022, 11, day).and_hms(h, m, s);
+        let night = "22:00-02:00".parse::<Window>().unwrap();
+
+        let boundaries = night.boundaries(today, Duration::minutes(15), Duration::zero());
+        assert_eq!(boundaries.len(), 10);
+        assert!(boundaries.contains(&at(3, 21, 45, 0)));
+        assert!(boundaries.contains(&at(4, 2, 0, 1)));
+    }
+
+    #[test]
+    fn should_wrap_windows_past_midnight() {
+        let offset = FixedOffset::west(3 * 3600);
+        let today = offset.ymd(2022, 11, 3);
+        let yesterday = offset.ymd(2022, 11, 2);
+        let window = |value: &str| value.parse::<Window>().unwrap();
+        let time = |value: &str| parse_time(value).unwrap();
+
+        let day = window("06:00-18:00");
+        assert_eq!(day.day(today, time("06:00")), Some(today));
+        assert_eq!(day.day(today, time("18:00:01")), None);
+
+        let night = window("22:00-02:00");
+        assert_eq!(night.day(today, time("23:00")), Some(today));
+        assert_eq!(night.day(today, time("01:00")), Some(yesterday));
+        assert_eq!(night.day(today, time("02:00")), Some(yesterday));
+        assert_eq!(night.day(today, time("12:00")), None);
+
+        let whole_day = window("06:00-06:00");
+        assert_eq!(whole_day.day(today, time("06:00")), Some(today));
+        assert_eq!(whole_day.day(today, time("05:59:59")), Some(yesterday));
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use tokio::sync::{mpsc, Notify};
+use tonic::Status;
+use tracing::{info, warn};
+
+use crate::calendars::Calendars;
+use crate::handler::{is_active, next_transition, IsActiveResponse, ScaledObjectRef};
+use crate::profiles::Profiles;
+
+/// Wait before evaluating again a schedule whose next transition is unknown, like on errors.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Messages buffered for each subscriber before pushes are dropped.
+const SUBSCRIBER_BUFFER: usize = 16;
+
+type Subscriber = mpsc::Sender<Result<IsActiveResponse, Status>>;
+
+/// Evaluates `StreamIsActive` schedules once for every subscriber with the same metadata. Each
+/// schedule runs on its own task until its last subscriber leaves, and is evaluated again right
+/// away when the calendars are reloaded.
+#[derive(Debug)]
+pub struct Scheduler {
+    calendars: Arc<Calendars>,
+    profiles: Arc<Profiles>,
+    heartbeat: Option<Duration>,
+    schedules: Mutex<HashMap<ScheduleKey, Arc<SharedSchedule>>>,
+}
+
+/// Metadata of the subscribers of a schedule.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ScheduleKey(BTreeMap<String, String>);
+
+impl ScheduleKey {
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.0
+    }
+}
+
+#[derive(Debug, Default)]
+struct SharedSchedule {
+    state: Mutex<SharedState>,
+    unsubscribed: Notify,
+}
+
+#[derive(Debug, Default)]
+struct SharedState {
+    subscribers: Vec<Subscriber>,
+    last_result: Option<Result<IsActiveResponse, Status>>,
+}
+
+impl Scheduler {
+    /// Scheduler repeating the current state every `push_interval` seconds, `0` to only push
+    /// changes.
+    pub fn new(calendars: Arc<Calendars>, profiles: Arc<Profiles>, push_interval: u64) -> Self {
+        Scheduler {
+            calendars,
+            profiles,
+            heartbeat: Some(push_interval)
+                .filter(|push_interval| *push_interval > 0)
+                .map(Duration::from_secs),
+            schedules: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Subscribes to the schedule of the message, started when it is the first subscriber.
+    /// Later subscribers get the last result right away.
+    pub fn subscribe(
+        self: &Arc<Self>,
+        message: ScaledObjectRef,
+    ) -> mpsc::Receiver<Result<IsActiveResponse, Status>> {
+        let (tx, rx) = mpsc::channel(SUBSCRIBER_BUFFER);
+        let key = ScheduleKey(message.scaler_metadata.clone().into_iter().collect());
+
+        let mut schedules = self.schedules.lock().unwrap();
+        let (shared, running) = match schedules.get(&key) {
+            Some(shared) => (Arc::clone(shared), true),
+            None => {
+                let shared = Arc::new(SharedSchedule::default());
+                schedules.insert(key.clone(), Arc::clone(&shared));
+                (shared, false)
+            }
+        };
+
+        let mut state = shared.state.lock().unwrap();
+        if let Some(result) = state.last_result.clone() {
+            let _ = tx.try_send(result);
+        }
+        state.subscribers.push(tx.clone());
+        info!(
+            "{} subscribers attached to schedule {}.",
+            state.subscribers.len(),
+            key
+        );
+        drop(state);
+
+        if !running {
+            tokio::spawn(Arc::clone(self).run(key, message, Arc::clone(&shared)));
+        }
+
+        let watched = Arc::clone(&shared);
+        tokio::spawn(async move {
+            tx.closed().await;
+            watched.unsubscribed.notify_one();
+        });
+
+        rx
+    }
+
+    /// Number of subscribers attached to each schedule, served by `ListSchedules`.
+    pub fn subscribers(&self) -> Vec<(ScheduleKey, usize)> {
+        let schedules = self.schedules.lock().unwrap();
+        schedules
+            .iter()
+            .map(|(key, shared)| (key.clone(), shared.state.lock().unwrap().subscribers.len()))
+            .collect()
+    }
+
+    async fn run(
+        self: Arc<Self>,
+        key: ScheduleKey,
+        message: ScaledObjectRef,
+        shared: Arc<SharedSchedule>,
+    ) {
+        let mut reloads = self.calendars.subscribe_reloads();
+        let mut last_state = None;
+        let mut last_push = Instant::now();
+        loop {
+            let now = Utc::now();
+            let result = is_active(&self.calendars, &self.profiles, message.clone(), &now).await;
+            let state = result.as_ref().ok().map(|response| response.result);
+            let heartbeat_due = self
+                .heartbeat
+                .is_some_and(|heartbeat| last_push.elapsed() >= heartbeat);
+
+            if state.is_none() || state != last_state || heartbeat_due {
+                shared.publish(result);
+                last_state = state;
+                last_push = Instant::now();
+            }
+
+            if self.release_if_unused(&key, &shared) {
+                break;
+            }
+
+            let mut wait = next_transition(&self.calendars, &self.profiles, &message, &now)
+                .ok()
+                .flatten()
+                .map(|instant| (instant - Utc::now()).to_std().unwrap_or_default())
+                .unwrap_or(RETRY_INTERVAL);
+            if let Some(heartbeat) = self.heartbeat {
+                wait = wait.min(heartbeat.saturating_sub(last_push.elapsed()));
+            }
+
+            tokio::select! {
+                _ = shared.unsubscribed.notified() => {}
+                Ok(()) = reloads.changed() => {}
+                _ = tokio::time::sleep(wait) => {}
+            }
+        }
+    }
+
+    /// Drops closed subscribers, and the schedule once none is left.
+    fn release_if_unused(&self, key: &ScheduleKey, shared: &SharedSchedule) -> bool {
+        let mut schedules = self.schedules.lock().unwrap();
+        let mut state = shared.state.lock().unwrap();
+        let subscribers = &mut state.subscribers;
+        let attached = subscribers.len();
+        subscribers.retain(|subscriber| !subscriber.is_closed());
+        if subscribers.len() != attached {
+            info!(
+                "{} subscribers attached to schedule {}.",
+                subscribers.len(),
+                key
+            );
+        }
+
+        if subscribers.is_empty() {
+            schedules.remove(key);
+            return true;
+        }
+        false
+    }
+}
+
+impl SharedSchedule {
+    fn publish(&self, result: Result<IsActiveResponse, Status>) {
+        let mut state = self.state.lock().unwrap();
+        for subscriber in &state.subscribers {
+            if let Err(mpsc::error::TrySendError::Full(_)) = subscriber.try_send(result.clone()) {
+                warn!("Subscriber is not reading StreamIsActive messages, dropping one.");
+            }
+        }
+        state.last_result = Some(result);
+    }
+}
+
+impl Display for ScheduleKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let metadata: Vec<String> = self
+            .0
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        write!(f, "{{{}}}", metadata.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, TimeZone};
+
+    use crate::working_days::{Weekend, WorkingDays};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_share_schedules_between_subscribers() {
+        let offset = FixedOffset::west(3 * 3600);
+        let working_days = WorkingDays::build(
+            offset,
+            Weekend::default(),
+            vec![offset.ymd(2022, 6, 5).into(), offset.ymd(2122, 6, 5).into()],
+        )
+        .unwrap();
+        let scheduler = Arc::new(Scheduler::new(
+            Arc::new(Calendars::single(working_days)),
+            Arc::new(Profiles::default()),
+            0,
+        ));
+        let message = |nth_working_day: &str| {
+            let mut metadata = HashMap::new();
+            metadata.insert("nthWorkingDay".to_string(), nth_working_day.to_string());
+            metadata.insert("fromTime".to_string(), "06:00:00".to_string());
+            metadata.insert("toTime".to_string(), "18:00:00".to_string());
+            metadata.insert("targetSize".to_string(), "10".to_string());
+            ScaledObjectRef {
+                name: "name".to_string(),
+                namespace: "namespace".to_string(),
+                scaler_metadata: metadata,
+            }
+        };
+
+        let mut first = scheduler.subscribe(message("5"));
+        let first_result = first.recv().await.unwrap().unwrap();
+        let mut second = scheduler.subscribe(message("5"));
+        let mut other = scheduler.subscribe(message("-1"));
+
+        assert_eq!(second.recv().await.unwrap().unwrap(), first_result);
+        assert!(other.recv().await.unwrap().is_ok());
+
+        let mut subscribers: Vec<usize> = scheduler
+            .subscribers()
+            .into_iter()
+            .map(|(_, count)| count)
+            .collect();
+        subscribers.sort();
+        assert_eq!(subscribers, vec![1, 2]);
+
+        drop(other);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let subscribers = scheduler.subscribers();
+        assert_eq!(subscribers.len(), 1);
+        assert_eq!(subscribers[0].1, 2);
+        assert_eq!(
+            subscribers[0].0.to_string(),
+            "{fromTime=06:00:00, nthWorkingDay=5, targetSize=10, toTime=18:00:00}"
+        );
+
+        drop(first);
+        drop(second);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(scheduler.subscribers().is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_evaluate_again_when_calendars_are_reloaded() {
+        let offset = FixedOffset::west(3 * 3600);
+        let holidays = |year| {
+            vec![
+                offset.ymd(year, 6, 5).into(),
+                offset.ymd(year + 100, 6, 5).into(),
+            ]
+        };
+        let calendars = Arc::new(Calendars::single(
+            WorkingDays::build(offset, Weekend::new(&[]), holidays(2022)).unwrap(),
+        ));
+        let scheduler = Arc::new(Scheduler::new(
+            Arc::clone(&calendars),
+            Arc::new(Profiles::default()),
+            0,
+        ));
+        let mut metadata = HashMap::new();
+        metadata.insert("nthWorkingDay".to_string(), "1-31".to_string());
+        metadata.insert("fromTime".to_string(), "00:00:00".to_string());
+        metadata.insert("toTime".to_string(), "00:00:00".to_string());
+        metadata.insert("targetSize".to_string(), "10".to_string());
+
+        let mut stream = scheduler.subscribe(ScaledObjectRef {
+            name: "name".to_string(),
+            namespace: "namespace".to_string(),
+            scaler_metadata: metadata,
+        });
+        assert!(stream.recv().await.unwrap().unwrap().result);
+
+        // The reloaded calendar no longer covers today
+        let reloaded = WorkingDays::build(offset, Weekend::new(&[]), holidays(1900)).unwrap();
+        calendars.swap(Calendars::single(reloaded));
+
+        let result = tokio::time::timeout(Duration::from_secs(1), stream.recv()).await;
+        assert!(result.unwrap().unwrap().is_err());
+    }
+}
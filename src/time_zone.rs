@@ -0,0 +1,159 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use thiserror::Error;
+
+/// Time zone used to read the current date and time of a calendar. Named zones follow their
+/// daylight saving time transitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalendarTimeZone {
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Invalid time zone `{0}`. Expected an IANA time zone name, like `America/New_York`.")]
+pub struct InvalidTimeZone(pub String);
+
+impl CalendarTimeZone {
+    /// Local date and time of the instant.
+    pub fn local(&self, instant: &DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            CalendarTimeZone::Fixed(offset) => instant.with_timezone(offset).naive_local(),
+            CalendarTimeZone::Named(tz) => instant.with_timezone(tz).naive_local(),
+        }
+    }
+
+    /// Instant of the local date and time, the earliest one when it happens twice. `None` for
+    /// times skipped by a daylight saving time transition.
+    pub fn instant(&self, local: &NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            CalendarTimeZone::Fixed(offset) => offset
+                .from_local_datetime(local)
+                .earliest()
+                .map(|instant| instant.with_timezone(&Utc)),
+            CalendarTimeZone::Named(tz) => tz
+                .from_local_datetime(local)
+                .earliest()
+                .map(|instant| instant.with_timezone(&Utc)),
+        }
+    }
+
+    /// Offset attached to the dates of the working days table. Named zones use their offset on
+    /// the first day of 2000, it only shows up when printing dates.
+    pub fn date_offset(&self) -> FixedOffset {
+        match self {
+            CalendarTimeZone::Fixed(offset) => *offset,
+            CalendarTimeZone::Named(tz) => tz
+                .offset_from_utc_date(&NaiveDate::from_ymd(2000, 1, 1))
+                .fix(),
+        }
+    }
+}
+
+impl From<FixedOffset> for CalendarTimeZone {
+    fn from(offset: FixedOffset) -> Self {
+        CalendarTimeZone::Fixed(offset)
+    }
+}
+
+impl FromStr for CalendarTimeZone {
+    type Err = InvalidTimeZone;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .trim()
+            .parse::<Tz>()
+            .map(CalendarTimeZone::Named)
+            .map_err(|_| InvalidTimeZone(value.to_string()))
+    }
+}
+
+impl Display for CalendarTimeZone {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CalendarTimeZone::Fixed(offset) => write!(f, "{}", offset),
+            CalendarTimeZone::Named(tz) => write!(f, "{}", tz.name()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveTime;
+    use chrono_tz::America::New_York;
+
+    use super::*;
+
+    #[test]
+    fn should_parse_and_display_time_zone() {
+        assert_eq!(
+            "America/New_York".parse::<CalendarTimeZone>(),
+            Ok(CalendarTimeZone::Named(New_York))
+        );
+        assert_eq!(
+            "America/Gotham".parse::<CalendarTimeZone>(),
+            Err(InvalidTimeZone("America/Gotham".to_string()))
+        );
+
+        assert_eq!(
+            CalendarTimeZone::Named(New_York).to_string(),
+            "America/New_York"
+        );
+        assert_eq!(
+            CalendarTimeZone::from(FixedOffset::west(3 * 3600)).to_string(),
+            "-03:00"
+        );
+        assert_eq!(
+            CalendarTimeZone::Named(New_York).date_offset(),
+            FixedOffset::west(5 * 3600)
+        );
+    }
+
+    #[test]
+    fn should_follow_daylight_saving_time() {
+        let new_york = CalendarTimeZone::Named(New_York);
+        let fixed = CalendarTimeZone::Fixed(FixedOffset::west(5 * 3600));
+
+        // Spring forward, 2023-03-12 02:00 EST becomes 03:00 EDT
+        let before = Utc.ymd(2023, 3, 12).and_hms(6, 30, 0);
+        let after = Utc.ymd(2023, 3, 12).and_hms(7, 30, 0);
+        assert_eq!(
+            new_york.local(&before).time(),
+            NaiveTime::from_hms(1, 30, 0)
+        );
+        assert_eq!(new_york.local(&after).time(), NaiveTime::from_hms(3, 30, 0));
+        assert_eq!(fixed.local(&after).time(), NaiveTime::from_hms(2, 30, 0));
+
+        // Fall back, 2023-11-05 02:00 EDT becomes 01:00 EST and 01:30 happens twice
+        let first = Utc.ymd(2023, 11, 5).and_hms(5, 30, 0);
+        let second = Utc.ymd(2023, 11, 5).and_hms(6, 30, 0);
+        assert_eq!(new_york.local(&first).time(), NaiveTime::from_hms(1, 30, 0));
+        assert_eq!(
+            new_york.local(&second).time(),
+            NaiveTime::from_hms(1, 30, 0)
+        );
+
+        // Local times skipped and repeated by the transitions
+        let local = |day, h| NaiveDate::from_ymd(2023, 3, day).and_hms(h, 30, 0);
+        assert_eq!(new_york.instant(&local(12, 2)), None);
+        assert_eq!(new_york.instant(&local(12, 3)), Some(after));
+        assert_eq!(
+            new_york.instant(&NaiveDate::from_ymd(2023, 11, 5).and_hms(1, 30, 0)),
+            Some(first)
+        );
+
+        // Local midnight moves the date an hour earlier in summer
+        let midnight = Utc.ymd(2023, 7, 3).and_hms(4, 30, 0);
+        assert_eq!(
+            new_york.local(&midnight).date(),
+            NaiveDate::from_ymd(2023, 7, 3)
+        );
+        assert_eq!(
+            fixed.local(&midnight).date(),
+            NaiveDate::from_ymd(2023, 7, 2)
+        );
+    }
+}
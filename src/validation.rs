@@ -0,0 +1,257 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Duration, FixedOffset, NaiveDate};
+use thiserror::Error;
+
+use crate::holiday::{DayType, Observance};
+use crate::holiday_rules;
+use crate::holidays_loader;
+use crate::holidays_loader::{HolidaysFormat, HolidaysLoaderError, LoadMode};
+use crate::working_days::{Weekend, WorkingDays, WorkingDaysError};
+
+/// Settings of the checks run on a holidays file before deploying it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationSettings {
+    pub weekend: Weekend,
+    pub observance: Observance,
+    /// Format of the holidays file, given by its extension when `None`.
+    pub holidays_format: Option<HolidaysFormat>,
+    /// Whether holidays on a weekend without an observance are reported.
+    pub require_observance: bool,
+    /// Years with fewer holidays are reported.
+    pub min_holidays: usize,
+    /// Working days should be covered at least this many days after today.
+    pub horizon_days: u32,
+}
+
+#[derive(Error, Debug)]
+pub enum ValidationProblem {
+    #[error(transparent)]
+    LoadingHolidays(HolidaysLoaderError),
+    #[error(transparent)]
+    ProcessingWorkingDays(WorkingDaysError),
+    #[error("Holiday {0} is on a weekend and has no observance.")]
+    HolidayOnWeekend(NaiveDate),
+    #[error("Only {} in {0}, expected at least {2}.", holidays(*.1))]
+    FewHolidays(i32, usize, usize),
+    #[error("Working days are covered until {0}, within the {1} days horizon.")]
+    CoverageEnding(NaiveDate, u32),
+}
+
+/// Loads the holidays file strictly and builds its working days, returning every problem found.
+pub fn validate(
+    holidays_file: &str,
+    settings: &ValidationSettings,
+    today: NaiveDate,
+) -> Vec<ValidationProblem> {
+    let offset = FixedOffset::east(0);
+    let format = settings
+        .holidays_format
+        .unwrap_or_else(|| HolidaysFormat::of(holidays_file));
+    let loaded = match holidays_loader::load_with_format(
+        format,
+        offset,
+        holidays_file,
+        settings.observance,
+        LoadMode::Strict,
+    ) {
+        Ok(loaded) => loaded,
+        Err(HolidaysLoaderError::InvalidHolidaysFile(_, problems)) => {
+            return problems
+                .into_iter()
+                .map(ValidationProblem::LoadingHolidays)
+                .collect()
+        }
+        Err(err) => return vec![ValidationProblem::LoadingHolidays(err)],
+    };
+
+    // Rules of recurring events are checked from their first year until the current one.
+    let years = loaded
+        .holidays
+        .iter()
+        .map(|holiday| holiday.date.year())
+        .chain(
+            loaded
+                .rules
+                .iter()
+                .filter_map(|rule| rule.since.map(|since| since.year())),
+        )
+        .chain(std::iter::once(today.year()));
+    let (first_year, last_year) = years.fold((i32::MAX, i32::MIN), |(first, last), year| {
+        (first.min(year), last.max(year))
+    });
+    let mut holidays = loaded.holidays;
+    holidays.extend(holiday_rules::expand(
+        &loaded.rules,
+        offset,
+        first_year,
+        last_year,
+    ));
+
+    let mut problems = Vec::new();
+    let mut per_year = BTreeMap::new();
+    for holiday in holidays
+        .iter()
+        .filter(|holiday| holiday.day_type == DayType::Holiday)
+    {
+        let date = holiday.date.naive_utc();
+        *per_year.entry(date.year()).or_insert(0) += 1;
+        if settings.require_observance
+            && holiday.observance == Observance::None
+            && settings.weekend.contains(date.weekday())
+        {
+            problems.push(ValidationProblem::HolidayOnWeekend(date));
+        }
+    }
+
+    if let (Some(first), Some(last)) = (per_year.keys().min(), per_year.keys().max()) {
+        for year in *first..=*last {
+            let count = per_year.get(&year).copied().unwrap_or_default();
+            if count < settings.min_holidays {
+                problems.push(ValidationProblem::FewHolidays(
+                    year,
+                    count,
+                    settings.min_holidays,
+                ));
+            }
+        }
+    }
+
+    // Working days with rules are extended on demand, their coverage never ends.
+    if !loaded.rules.is_empty() {
+        return problems;
+    }
+    match WorkingDays::build(offset, settings.weekend, holidays) {
+        Ok(working_days) => {
+            let end_date = working_days.end_date.naive_utc();
+            let horizon = today.checked_add_signed(Duration::days(settings.horizon_days.into()));
+            if horizon.is_none_or(|horizon| end_date < horizon) {
+                problems.push(ValidationProblem::CoverageEnding(
+                    end_date,
+                    settings.horizon_days,
+                ));
+            }
+        }
+        Err(err) => problems.push(ValidationProblem::ProcessingWorkingDays(err)),
+    }
+
+    problems
+}
+
+fn holidays(count: usize) -> String {
+    match count {
+        1 => "1 holiday".to_string(),
+        count => format!("{} holidays", count),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> ValidationSettings {
+        ValidationSettings {
+            weekend: Weekend::default(),
+            observance: Observance::None,
+            holidays_format: None,
+            require_observance: false,
+            min_holidays: 0,
+            horizon_days: 0,
+        }
+    }
+
+    fn messages(problems: Vec<ValidationProblem>) -> Vec<String> {
+        problems.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn should_accept_valid_holidays() {
+        let problems = validate(
+            "tests_resources/small_holidays.csv",
+            &ValidationSettings {
+                require_observance: true,
+                weekend: Weekend::new(&[]),
+                min_holidays: 12,
+                horizon_days: 60,
+                ..settings()
+            },
+            NaiveDate::from_ymd(2020, 11, 1),
+        );
+        assert!(problems.is_empty());
+    }
+
+    #[test]
+    fn should_report_loading_problems() {
+        let problems = validate(
+            "tests_resources/problem_holidays.csv",
+            &settings(),
+            NaiveDate::from_ymd(2022, 11, 1),
+        );
+        assert_eq!(
+            messages(problems),
+            vec![
+                "Invalid date format at line 4.",
+                "Duplicate date 2022-04-21 at line 5.",
+                "Invalid row at line 6.",
+                "Date 2022-02-28 out of order at line 7.",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_report_weekend_holidays_when_observance_is_required() {
+        let today = NaiveDate::from_ymd(2022, 11, 1);
+        let file = "tests_resources/suspicious_holidays.csv";
+
+        assert!(validate(file, &settings(), today).is_empty());
+
+        let problems = validate(
+            file,
+            &ValidationSettings {
+                require_observance: true,
+                ..settings()
+            },
+            today,
+        );
+        assert_eq!(
+            messages(problems),
+            vec!["Holiday 2022-12-25 is on a weekend and has no observance."]
+        );
+    }
+
+    #[test]
+    fn should_report_years_with_few_holidays() {
+        let problems = validate(
+            "tests_resources/sparse_holidays.csv",
+            &ValidationSettings {
+                min_holidays: 2,
+                ..settings()
+            },
+            NaiveDate::from_ymd(2020, 11, 1),
+        );
+        assert_eq!(
+            messages(problems),
+            vec![
+                "Only 0 holidays in 2021, expected at least 2.",
+                "Only 1 holiday in 2022, expected at least 2.",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_report_coverage_ending_within_the_horizon() {
+        let today = NaiveDate::from_ymd(2020, 11, 1);
+        let file = "tests_resources/small_holidays.csv";
+        let horizon = |horizon_days| ValidationSettings {
+            horizon_days,
+            ..settings()
+        };
+
+        assert!(validate(file, &horizon(60), today).is_empty());
+        assert_eq!(
+            messages(validate(file, &horizon(61), today)),
+            vec!["Working days are covered until 2020-12-31, within the 61 days horizon."]
+        );
+        assert_eq!(validate(file, &horizon(u32::MAX), today).len(), 1);
+    }
+}
@@ -1,20 +1,42 @@
-use chrono::{Date, Datelike, Duration, FixedOffset, Weekday};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use chrono::{Date, DateTime, Datelike, Duration, FixedOffset, NaiveTime, TimeZone, Utc, Weekday};
 use thiserror::Error;
 
 use WorkingDaysError::DateOutOfRange;
 
-use crate::working_days::WorkingDaysError::EmptyHolidayList;
+use crate::holiday::{
+    holiday_names, observed_days_off, workday_dates, working_weights, Holiday, Observance,
+};
+use crate::holiday_rules;
+use crate::holiday_rules::RecurringHoliday;
+use crate::time_zone::CalendarTimeZone;
+use crate::working_days::WorkingDaysError::{
+    EmptyComposition, EmptyHolidayList, InvalidWeekend, NoCommonRange,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct WorkingDays {
     pub time_offset: FixedOffset,
+    /// Time zone of the current date and time, the time offset unless set otherwise.
+    pub time_zone: CalendarTimeZone,
+    pub weekend: Weekend,
     pub start_date: Date<FixedOffset>,
     pub end_date: Date<FixedOffset>,
+    holidays: Vec<Holiday>,
+    rules: Vec<RecurringHoliday>,
     data_offset: usize,
     data: Vec<u8>,
+    remaining: Vec<u8>,
+    cumulative: Vec<u32>,
+    weights: BTreeMap<Date<FixedOffset>, f64>,
+    weighted: Vec<f64>,
+    names: BTreeMap<Date<FixedOffset>, String>,
 }
 
-#[derive(Error, Debug, PartialEq, Eq)]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
 pub enum WorkingDaysError {
     #[error("The holiday list is empty. Its also used to infer witch years to process.")]
     EmptyHolidayList,
@@ -22,52 +44,479 @@ pub enum WorkingDaysError {
         "The requested date was not calculated. Table processed for dates between {0} and {1}."
     )]
     DateOutOfRange(Date<FixedOffset>, Date<FixedOffset>),
+    #[error("Invalid weekend `{0}`. Expected `none` or a comma separated list of weekdays.")]
+    InvalidWeekend(String),
+    #[error("A composed calendar needs at least one calendar.")]
+    EmptyComposition,
+    #[error("The composed calendars do not cover any common date.")]
+    NoCommonRange,
+}
+
+/// How a composed calendar merges the holidays of its calendars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Composition {
+    /// Holidays of any calendar, working days being the days open in every calendar.
+    Union,
+    /// Holidays of every calendar, working days being the days open in any calendar.
+    Intersection,
+}
+
+/// Set of weekdays that are not working days, Saturday and Sunday by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Weekend(u8);
+
+impl Weekend {
+    pub fn new(weekdays: &[Weekday]) -> Self {
+        Weekend(
+            weekdays
+                .iter()
+                .fold(0, |mask, weekday| mask | weekday_bit(weekday)),
+        )
+    }
+
+    pub fn contains(&self, weekday: Weekday) -> bool {
+        self.0 & weekday_bit(&weekday) != 0
+    }
+
+    pub fn union(self, other: Weekend) -> Weekend {
+        Weekend(self.0 | other.0)
+    }
+
+    pub fn intersection(self, other: Weekend) -> Weekend {
+        Weekend(self.0 & other.0)
+    }
+}
+
+impl Default for Weekend {
+    fn default() -> Self {
+        Weekend::new(&[Weekday::Sat, Weekday::Sun])
+    }
+}
+
+impl FromStr for Weekend {
+    type Err = WorkingDaysError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value.trim().eq_ignore_ascii_case("none") {
+            return Ok(Weekend::new(&[]));
+        }
+
+        let weekdays = value
+            .split(',')
+            .map(|weekday| weekday.trim().parse::<Weekday>())
+            .collect::<Result<Vec<Weekday>, _>>()
+            .map_err(|_| InvalidWeekend(value.to_string()))?;
+
+        Ok(Weekend::new(&weekdays))
+    }
+}
+
+impl Display for Weekend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let weekdays: Vec<String> = WEEKDAYS
+            .iter()
+            .filter(|weekday| self.contains(**weekday))
+            .map(|weekday| weekday.to_string().to_lowercase())
+            .collect();
+
+        if weekdays.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", weekdays.join(","))
+        }
+    }
+}
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+fn weekday_bit(weekday: &Weekday) -> u8 {
+    1 << weekday.num_days_from_monday()
 }
 
 impl WorkingDays {
     pub fn build(
         time_offset: FixedOffset,
-        holidays: Vec<Date<FixedOffset>>,
+        weekend: Weekend,
+        holidays: Vec<Holiday>,
     ) -> Result<WorkingDays, WorkingDaysError> {
-        if holidays.is_empty() {
-            Err(EmptyHolidayList)
-        } else {
-            let start_date = at_start_of_year(holidays.first().unwrap());
-            let end_date = at_end_of_year(holidays.last().unwrap());
+        let first = holidays.iter().map(|holiday| holiday.date).min();
+        let last = holidays.iter().map(|holiday| holiday.date).max();
+
+        if let (Some(first), Some(last)) = (first, last) {
+            let start_date = at_start_of_year(&first);
+            let end_date = at_end_of_year(&last);
             Ok(Self::build_with_range(
                 time_offset,
+                weekend,
                 start_date,
                 end_date,
                 holidays,
             ))
+        } else {
+            Err(EmptyHolidayList)
         }
     }
 
     pub fn build_with_range(
         time_offset: FixedOffset,
+        weekend: Weekend,
+        start_date: Date<FixedOffset>,
+        end_date: Date<FixedOffset>,
+        holidays: Vec<Holiday>,
+    ) -> Self {
+        Self::build_with_sources(
+            time_offset,
+            weekend,
+            start_date,
+            end_date,
+            holidays,
+            Vec::new(),
+        )
+    }
+
+    /// Builds the table between the first and the last year with the holidays plus the rules
+    /// expanded for those years. The range grows to cover every holiday of the list. Tables
+    /// with rules can later be extended with `extend_to`.
+    pub fn build_with_rules(
+        time_offset: FixedOffset,
+        weekend: Weekend,
+        holidays: Vec<Holiday>,
+        rules: Vec<RecurringHoliday>,
+        first_year: i32,
+        last_year: i32,
+    ) -> Self {
+        let first_year = holidays
+            .iter()
+            .map(|holiday| holiday.date.year())
+            .fold(first_year, i32::min);
+        let last_year = holidays
+            .iter()
+            .map(|holiday| holiday.date.year())
+            .fold(last_year, i32::max);
+
+        Self::build_with_sources(
+            time_offset,
+            weekend,
+            time_offset.ymd(first_year, 1, 1),
+            time_offset.ymd(last_year, 12, 31),
+            holidays,
+            rules,
+        )
+    }
+
+    fn build_with_sources(
+        time_offset: FixedOffset,
+        weekend: Weekend,
         start_date: Date<FixedOffset>,
         end_date: Date<FixedOffset>,
-        mut holidays: Vec<Date<FixedOffset>>,
+        mut holidays: Vec<Holiday>,
+        rules: Vec<RecurringHoliday>,
     ) -> Self {
-        holidays.sort();
+        holidays.sort_by_key(|holiday| holiday.date);
+
+        let mut all_holidays = holidays.clone();
+        all_holidays.extend(holiday_rules::expand(
+            &rules,
+            time_offset,
+            start_date.year(),
+            end_date.year(),
+        ));
+        let workdays = workday_dates(&all_holidays);
+        let weights = working_weights(&all_holidays);
+        let days_off = observed_days_off(&all_holidays, weekend);
+        let names = holiday_names(&all_holidays, &days_off);
+        let holiday_dates = days_off.into_keys().collect();
 
         let data_offset = start_date.num_days_from_ce() as usize;
-        let data = process_working_days(&start_date, &end_date, holidays);
+        let data = process_working_days(&start_date, &end_date, weekend, holiday_dates, workdays);
+        let remaining = process_remaining_working_days(&start_date, &data);
+        let cumulative = process_cumulative_working_days(&start_date, &data);
+        let weighted = process_weighted_working_days(&start_date, &data, &weights);
 
         WorkingDays {
             time_offset,
+            time_zone: time_offset.into(),
+            weekend,
             start_date,
             end_date,
+            holidays,
+            rules,
             data_offset,
             data,
+            remaining,
+            cumulative,
+            weights,
+            weighted,
+            names,
+        }
+    }
+
+    /// Builds a table over the dates covered by every calendar, in the time offset of the
+    /// first one. Days are weighted with the lowest weight among the calendars for a union and
+    /// the highest for an intersection. The weekend is merged the same way. The time zone is
+    /// also the one of the first calendar.
+    pub fn compose(
+        composition: Composition,
+        calendars: &[&WorkingDays],
+    ) -> Result<WorkingDays, WorkingDaysError> {
+        let first = calendars.first().ok_or(EmptyComposition)?;
+        let time_offset = first.time_offset;
+
+        let start = calendars
+            .iter()
+            .map(|calendar| calendar.start_date.naive_local())
+            .max()
+            .unwrap();
+        let end = calendars
+            .iter()
+            .map(|calendar| calendar.end_date.naive_local())
+            .min()
+            .unwrap();
+        if start > end {
+            return Err(NoCommonRange);
+        }
+
+        let weekend = calendars
+            .iter()
+            .map(|calendar| calendar.weekend)
+            .reduce(|weekend, other| match composition {
+                Composition::Union => weekend.union(other),
+                Composition::Intersection => weekend.intersection(other),
+            })
+            .unwrap();
+
+        let mut holidays = Vec::new();
+        for date in start.iter_days().take_while(|date| *date <= end) {
+            let mut weights = calendars
+                .iter()
+                .map(|calendar| calendar.working_weight(calendar.time_offset.from_utc_date(&date)));
+            let weight = match composition {
+                Composition::Union => weights.try_fold(1.0, |min, weight| {
+                    weight.map(|weight| f64::min(min, weight))
+                }),
+                Composition::Intersection => weights.try_fold(0.0, |max, weight| {
+                    weight.map(|weight| f64::max(max, weight))
+                }),
+            }?;
+
+            let names: Vec<&str> = calendars
+                .iter()
+                .filter_map(|calendar| {
+                    calendar.holiday_name(calendar.time_offset.from_utc_date(&date))
+                })
+                .collect();
+            let name = Some(names.join(", ")).filter(|name| !name.is_empty());
+
+            let date = time_offset.from_utc_date(&date);
+            let is_weekend = weekend.contains(date.weekday());
+            if weight <= 0.0 && !is_weekend {
+                holidays.push(Holiday::from(date).with_name(name));
+            } else if weight > 0.0 && is_weekend {
+                holidays.push(Holiday::workday(date).with_weight(weight).with_name(name));
+            } else if weight > 0.0 && weight < 1.0 {
+                let holiday = Holiday::new(date, Observance::None).with_weight(weight);
+                holidays.push(holiday.with_name(name));
+            }
+        }
+
+        Ok(Self::build_with_range(
+            time_offset,
+            weekend,
+            time_offset.from_utc_date(&start),
+            time_offset.from_utc_date(&end),
+            holidays,
+        )
+        .with_time_zone(first.time_zone))
+    }
+
+    pub fn with_time_zone(self, time_zone: CalendarTimeZone) -> Self {
+        WorkingDays { time_zone, ..self }
+    }
+
+    /// Current date, in the table time offset, and time in the time zone of the calendar.
+    pub fn local_now(&self, now: &DateTime<Utc>) -> (Date<FixedOffset>, NaiveTime) {
+        self.local_now_in(self.time_zone, now)
+    }
+
+    /// Same as `local_now`, but in another time zone.
+    pub fn local_now_in(
+        &self,
+        time_zone: CalendarTimeZone,
+        now: &DateTime<Utc>,
+    ) -> (Date<FixedOffset>, NaiveTime) {
+        let local = time_zone.local(now);
+        (self.time_offset.from_utc_date(&local.date()), local.time())
+    }
+
+    pub fn covers(&self, date: Date<FixedOffset>) -> bool {
+        self.index(date).is_ok()
+    }
+
+    pub fn is_extendable(&self) -> bool {
+        !self.rules.is_empty()
+    }
+
+    /// Rebuilds the table to also cover the whole year of the date, expanding the holiday rules
+    /// for the new years. Tables without rules cannot be extended.
+    pub fn extend_to(&mut self, date: Date<FixedOffset>) -> Result<(), WorkingDaysError> {
+        if self.covers(date) {
+            return Ok(());
         }
+        if !self.is_extendable() {
+            return Err(DateOutOfRange(self.start_date, self.end_date));
+        }
+
+        *self = Self::build_with_sources(
+            self.time_offset,
+            self.weekend,
+            self.start_date.min(at_start_of_year(&date)),
+            self.end_date.max(at_end_of_year(&date)),
+            std::mem::take(&mut self.holidays),
+            std::mem::take(&mut self.rules),
+        )
+        .with_time_zone(self.time_zone);
+
+        Ok(())
     }
 
     pub fn working_days_mtd(&self, date: Date<FixedOffset>) -> Result<u8, WorkingDaysError> {
+        let index = self.index(date)?;
+        Ok(*self.data.get(index).unwrap())
+    }
+
+    /// Month to date sum of the worked fraction of each working day, half days adding 0.5.
+    pub fn weighted_working_days_mtd(
+        &self,
+        date: Date<FixedOffset>,
+    ) -> Result<f64, WorkingDaysError> {
+        let index = self.index(date)?;
+        Ok(*self.weighted.get(index).unwrap())
+    }
+
+    /// Working days from the date, inclusive, until the end of its month. The last working
+    /// day of the month has 1, as well as the non-working days just before it.
+    pub fn working_days_until_eom(&self, date: Date<FixedOffset>) -> Result<u8, WorkingDaysError> {
+        let index = self.index(date)?;
+        Ok(*self.remaining.get(index).unwrap())
+    }
+
+    /// Date of the nth working day of the month. Negative values count from the end of the
+    /// month, -1 being the last working day.
+    pub fn nth_working_day_date(&self, year: i32, month: u32, n: i8) -> Option<Date<FixedOffset>> {
+        let mut current_date = self.time_offset.ymd_opt(year, month, 1).single()?;
+        let mut found = None;
+
+        while current_date.month() == month {
+            let index = self.index(current_date).ok()?;
+            if n > 0 && self.data[index] == n as u8 {
+                return Some(current_date);
+            }
+            if n < 0 && self.remaining[index] == n.unsigned_abs() {
+                found = Some(current_date);
+            }
+            current_date += Duration::days(1);
+        }
+
+        found
+    }
+
+    pub fn is_working_day(&self, date: Date<FixedOffset>) -> Result<bool, WorkingDaysError> {
+        let index = self.index(date)?;
+        Ok(self.is_working_index(index))
+    }
+
+    /// First working day after the date.
+    pub fn next_working_day(
+        &self,
+        date: Date<FixedOffset>,
+    ) -> Result<Date<FixedOffset>, WorkingDaysError> {
+        self.add_working_days(date, 1)
+    }
+
+    /// Last working day before the date.
+    pub fn previous_working_day(
+        &self,
+        date: Date<FixedOffset>,
+    ) -> Result<Date<FixedOffset>, WorkingDaysError> {
+        self.add_working_days(date, -1)
+    }
+
+    /// Moves `n` working days forward, or backward when negative, not counting the date itself.
+    /// Zero returns the date unchanged.
+    pub fn add_working_days(
+        &self,
+        date: Date<FixedOffset>,
+        n: i32,
+    ) -> Result<Date<FixedOffset>, WorkingDaysError> {
+        let index = self.index(date)?;
+        let current = self.cumulative[index] as i64;
+
+        let target = match n {
+            0 => return Ok(date),
+            n if n > 0 => current + n as i64,
+            n if self.is_working_index(index) => current + n as i64,
+            n => current + n as i64 + 1,
+        };
+
+        let target_index = self
+            .cumulative
+            .partition_point(|count| (*count as i64) < target);
+        if target < 1 || target_index >= self.cumulative.len() {
+            Err(DateOutOfRange(self.start_date, self.end_date))
+        } else {
+            Ok(self.start_date + Duration::days(target_index as i64))
+        }
+    }
+
+    /// Working days after `from` until `to`, inclusive. Negative when `to` is before `from`.
+    pub fn working_days_between(
+        &self,
+        from: Date<FixedOffset>,
+        to: Date<FixedOffset>,
+    ) -> Result<i32, WorkingDaysError> {
+        let from_index = self.index(from)?;
+        let to_index = self.index(to)?;
+
+        Ok(self.cumulative[to_index] as i32 - self.cumulative[from_index] as i32)
+    }
+
+    /// Name of the holiday on the date, if any. Holidays observed on another date are named
+    /// `<name> (observed)` there.
+    pub fn holiday_name(&self, date: Date<FixedOffset>) -> Option<&str> {
+        self.names.get(&date).map(String::as_str)
+    }
+
+    /// Worked fraction of the date, zero for non-working days.
+    pub fn working_weight(&self, date: Date<FixedOffset>) -> Result<f64, WorkingDaysError> {
+        let index = self.index(date)?;
+        if self.is_working_index(index) {
+            Ok(self.weights.get(&date).copied().unwrap_or(1.0))
+        } else {
+            Ok(0.0)
+        }
+    }
+
+    fn is_working_index(&self, index: usize) -> bool {
+        let previous = if index == 0 {
+            0
+        } else {
+            self.cumulative[index - 1]
+        };
+        self.cumulative[index] != previous
+    }
+
+    fn index(&self, date: Date<FixedOffset>) -> Result<usize, WorkingDaysError> {
         let date_days = date.num_days_from_ce() as usize;
         if date_days >= self.data_offset && date_days < self.data_offset + self.data.len() {
-            let index = date.num_days_from_ce() as usize - self.data_offset;
-            Ok(*self.data.get(index).unwrap())
+            Ok(date_days - self.data_offset)
         } else {
             Err(DateOutOfRange(self.start_date, self.end_date))
         }
@@ -82,10 +531,13 @@ fn at_end_of_year(date: &Date<FixedOffset>) -> Date<FixedOffset> {
     date.with_month(12).unwrap().with_day(31).unwrap()
 }
 
+/// Make-up working days are counted even on a weekend or a holiday.
 fn process_working_days(
     start_date: &Date<FixedOffset>,
     end_date: &Date<FixedOffset>,
+    weekend: Weekend,
     holidays: Vec<Date<FixedOffset>>,
+    workdays: Vec<Date<FixedOffset>>,
 ) -> Vec<u8> {
     let data_size = end_date.num_days_from_ce() - start_date.num_days_from_ce();
     let mut data = Vec::with_capacity(data_size as usize);
@@ -95,9 +547,13 @@ fn process_working_days(
     let mut wd_count = 0;
     let mut holiday_iter = holidays.into_iter().filter(|date| date >= start_date);
     let mut next_holiday = holiday_iter.next();
+    let mut workday_iter = workdays.into_iter().filter(|date| date >= start_date);
+    let mut next_workday = workday_iter.next();
 
     while current_date <= *end_date {
-        if !is_weekend(&current_date) && Some(current_date) != next_holiday {
+        if Some(current_date) == next_workday
+            || (!weekend.contains(current_date.weekday()) && Some(current_date) != next_holiday)
+        {
             wd_count += 1;
         }
 
@@ -106,6 +562,9 @@ fn process_working_days(
         if Some(current_date) == next_holiday {
             next_holiday = holiday_iter.next()
         }
+        if Some(current_date) == next_workday {
+            next_workday = workday_iter.next()
+        }
 
         current_date += Duration::days(1);
         if current_date.month() != current_month {
@@ -117,20 +576,91 @@ fn process_working_days(
     data
 }
 
-fn is_weekend(date: &Date<FixedOffset>) -> bool {
-    matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+fn process_remaining_working_days(start_date: &Date<FixedOffset>, data: &[u8]) -> Vec<u8> {
+    let mut remaining = vec![0; data.len()];
+    let mut wd_count = 0;
+
+    for index in (0..data.len()).rev() {
+        let current_date = *start_date + Duration::days(index as i64);
+        if (current_date + Duration::days(1)).month() != current_date.month() {
+            wd_count = 0
+        }
+
+        let previous_count = if index == 0 || current_date.day() == 1 {
+            0
+        } else {
+            data[index - 1]
+        };
+        if data[index] != previous_count {
+            wd_count += 1;
+        }
+
+        remaining[index] = wd_count;
+    }
+
+    remaining
+}
+
+fn process_cumulative_working_days(start_date: &Date<FixedOffset>, data: &[u8]) -> Vec<u32> {
+    let mut cumulative = Vec::with_capacity(data.len());
+    let mut wd_count = 0;
+
+    for (index, count) in data.iter().enumerate() {
+        let current_date = *start_date + Duration::days(index as i64);
+        let previous_count = if index == 0 || current_date.day() == 1 {
+            0
+        } else {
+            data[index - 1]
+        };
+        if *count != previous_count {
+            wd_count += 1;
+        }
+
+        cumulative.push(wd_count);
+    }
+
+    cumulative
+}
+
+/// Working days weigh 1.0 unless listed with another weight. Non-working days weigh nothing.
+fn process_weighted_working_days(
+    start_date: &Date<FixedOffset>,
+    data: &[u8],
+    weights: &BTreeMap<Date<FixedOffset>, f64>,
+) -> Vec<f64> {
+    let mut weighted = Vec::with_capacity(data.len());
+    let mut wd_sum = 0.0;
+
+    for (index, count) in data.iter().enumerate() {
+        let current_date = *start_date + Duration::days(index as i64);
+        let previous_count = if index == 0 || current_date.day() == 1 {
+            wd_sum = 0.0;
+            0
+        } else {
+            data[index - 1]
+        };
+        if *count != previous_count {
+            wd_sum += weights.get(&current_date).copied().unwrap_or(1.0);
+        }
+
+        weighted.push(wd_sum);
+    }
+
+    weighted
 }
 
 #[cfg(test)]
 mod tests {
     use chrono::TimeZone;
 
+    use crate::holiday_rules::HolidayRule;
+
     use super::*;
 
     #[test]
     fn should_require_a_holiday_list_not_empty() {
         let offset = FixedOffset::west(3 * 3600);
-        let result = WorkingDays::build(offset, Vec::new());
+        let result = WorkingDays::build(offset, Weekend::default(), Vec::new());
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), EmptyHolidayList)
     }
@@ -140,10 +670,10 @@ mod tests {
         let mut holidays = Vec::new();
         let offset = FixedOffset::west(3 * 3600);
 
-        holidays.push(offset.ymd(2020, 6, 5));
-        holidays.push(offset.ymd(2021, 6, 5));
+        holidays.push(offset.ymd(2020, 6, 5).into());
+        holidays.push(offset.ymd(2021, 6, 5).into());
 
-        let working_days = WorkingDays::build(offset, holidays).unwrap();
+        let working_days = WorkingDays::build(offset, Weekend::default(), holidays).unwrap();
 
         assert_eq!(working_days.start_date, offset.ymd(2020, 1, 1));
         assert_eq!(working_days.end_date, offset.ymd(2021, 12, 31));
@@ -154,16 +684,22 @@ mod tests {
         let mut holidays = Vec::new();
         let offset = FixedOffset::west(3 * 3600);
 
-        holidays.push(offset.ymd(2020, 6, 5));
-        holidays.push(offset.ymd(2021, 6, 5));
+        holidays.push(offset.ymd(2020, 6, 5).into());
+        holidays.push(offset.ymd(2021, 6, 5).into());
 
-        let working_days = WorkingDays::build(offset, holidays).unwrap();
+        let working_days = WorkingDays::build(offset, Weekend::default(), holidays).unwrap();
 
         let before = working_days.working_days_mtd(offset.ymd(2019, 12, 31));
         assert!(before.is_err());
 
         let after = working_days.working_days_mtd(offset.ymd(2022, 1, 1));
         assert!(after.is_err());
+
+        let before = working_days.working_days_until_eom(offset.ymd(2019, 12, 31));
+        assert!(before.is_err());
+
+        let after = working_days.working_days_until_eom(offset.ymd(2022, 1, 1));
+        assert!(after.is_err());
     }
 
     #[test]
@@ -171,20 +707,20 @@ mod tests {
         let mut holidays = Vec::new();
         let offset = FixedOffset::west(3 * 3600);
 
-        holidays.push(offset.ymd(2022, 1, 1));
-        holidays.push(offset.ymd(2022, 2, 28));
-        holidays.push(offset.ymd(2022, 3, 1));
-        holidays.push(offset.ymd(2022, 4, 15));
-        holidays.push(offset.ymd(2022, 4, 21));
-        holidays.push(offset.ymd(2022, 5, 1));
-        holidays.push(offset.ymd(2022, 6, 16));
-        holidays.push(offset.ymd(2022, 9, 7));
-        holidays.push(offset.ymd(2022, 10, 12));
-        holidays.push(offset.ymd(2022, 11, 2));
-        holidays.push(offset.ymd(2022, 11, 15));
-        holidays.push(offset.ymd(2022, 12, 25));
+        holidays.push(offset.ymd(2022, 1, 1).into());
+        holidays.push(offset.ymd(2022, 2, 28).into());
+        holidays.push(offset.ymd(2022, 3, 1).into());
+        holidays.push(offset.ymd(2022, 4, 15).into());
+        holidays.push(offset.ymd(2022, 4, 21).into());
+        holidays.push(offset.ymd(2022, 5, 1).into());
+        holidays.push(offset.ymd(2022, 6, 16).into());
+        holidays.push(offset.ymd(2022, 9, 7).into());
+        holidays.push(offset.ymd(2022, 10, 12).into());
+        holidays.push(offset.ymd(2022, 11, 2).into());
+        holidays.push(offset.ymd(2022, 11, 15).into());
+        holidays.push(offset.ymd(2022, 12, 25).into());
 
-        let working_days = WorkingDays::build(offset, holidays).unwrap();
+        let working_days = WorkingDays::build(offset, Weekend::default(), holidays).unwrap();
 
         let june: Vec<u8> = vec![
             1, 2, 3, 3, 3, 4, 5, 6, 7, 8, 8, 8, 9, 10, 11, 11, 12, 12, 12, 13, 14, 15, 16, 17, 17,
@@ -206,4 +742,537 @@ mod tests {
             current_date += Duration::days(1);
         }
     }
+
+    #[test]
+    fn should_calculate_working_days_until_end_of_month() {
+        let mut holidays = Vec::new();
+        let offset = FixedOffset::west(3 * 3600);
+
+        holidays.push(offset.ymd(2022, 6, 16).into());
+        holidays.push(offset.ymd(2022, 11, 2).into());
+        holidays.push(offset.ymd(2022, 11, 15).into());
+
+        let working_days = WorkingDays::build(offset, Weekend::default(), holidays).unwrap();
+
+        let june: Vec<u8> = vec![
+            21, 20, 19, 18, 18, 18, 17, 16, 15, 14, 13, 13, 13, 12, 11, 10, 10, 9, 9, 9, 8, 7, 6,
+            5, 4, 4, 4, 3, 2, 1,
+        ];
+        let mut current_date = offset.ymd(2022, 6, 1);
+        for wds in june {
+            assert_eq!(
+                working_days.working_days_until_eom(current_date).unwrap(),
+                wds
+            );
+            current_date += Duration::days(1);
+        }
+
+        let july_end: Vec<u8> = vec![2, 1, 0, 0];
+        let mut current_date = offset.ymd(2022, 7, 28);
+        for wds in july_end {
+            assert_eq!(
+                working_days.working_days_until_eom(current_date).unwrap(),
+                wds
+            );
+            current_date += Duration::days(1);
+        }
+
+        let november_end: Vec<u8> = vec![3, 3, 3, 2, 1];
+        let mut current_date = offset.ymd(2022, 11, 26);
+        for wds in november_end {
+            assert_eq!(
+                working_days.working_days_until_eom(current_date).unwrap(),
+                wds
+            );
+            current_date += Duration::days(1);
+        }
+    }
+
+    #[test]
+    fn should_find_the_date_of_the_nth_working_day() {
+        let mut holidays = Vec::new();
+        let offset = FixedOffset::west(3 * 3600);
+
+        holidays.push(offset.ymd(2022, 11, 2).into());
+        holidays.push(offset.ymd(2022, 11, 15).into());
+
+        let working_days = WorkingDays::build(offset, Weekend::default(), holidays).unwrap();
+
+        assert_eq!(
+            working_days.nth_working_day_date(2022, 11, 1),
+            Some(offset.ymd(2022, 11, 1))
+        );
+        assert_eq!(
+            working_days.nth_working_day_date(2022, 11, 2),
+            Some(offset.ymd(2022, 11, 3))
+        );
+        assert_eq!(
+            working_days.nth_working_day_date(2022, 11, 10),
+            Some(offset.ymd(2022, 11, 16))
+        );
+        assert_eq!(
+            working_days.nth_working_day_date(2022, 11, 20),
+            Some(offset.ymd(2022, 11, 30))
+        );
+        assert_eq!(working_days.nth_working_day_date(2022, 11, 21), None);
+
+        assert_eq!(
+            working_days.nth_working_day_date(2022, 11, -1),
+            Some(offset.ymd(2022, 11, 30))
+        );
+        assert_eq!(
+            working_days.nth_working_day_date(2022, 11, -4),
+            Some(offset.ymd(2022, 11, 25))
+        );
+        assert_eq!(
+            working_days.nth_working_day_date(2022, 11, -20),
+            Some(offset.ymd(2022, 11, 1))
+        );
+        assert_eq!(working_days.nth_working_day_date(2022, 11, -21), None);
+
+        assert_eq!(working_days.nth_working_day_date(2022, 11, 0), None);
+        assert_eq!(working_days.nth_working_day_date(2022, 13, 1), None);
+        assert_eq!(working_days.nth_working_day_date(2023, 1, 1), None);
+    }
+
+    #[test]
+    fn should_do_working_days_arithmetic() {
+        let mut holidays = Vec::new();
+        let offset = FixedOffset::west(3 * 3600);
+
+        holidays.push(offset.ymd(2022, 11, 2).into());
+        holidays.push(offset.ymd(2022, 11, 15).into());
+
+        let working_days = WorkingDays::build(offset, Weekend::default(), holidays).unwrap();
+
+        assert!(working_days
+            .is_working_day(offset.ymd(2022, 11, 1))
+            .unwrap());
+        assert!(!working_days
+            .is_working_day(offset.ymd(2022, 11, 2))
+            .unwrap());
+        assert!(!working_days
+            .is_working_day(offset.ymd(2022, 11, 5))
+            .unwrap());
+
+        assert_eq!(
+            working_days.next_working_day(offset.ymd(2022, 11, 1)),
+            Ok(offset.ymd(2022, 11, 3))
+        );
+        assert_eq!(
+            working_days.next_working_day(offset.ymd(2022, 11, 12)),
+            Ok(offset.ymd(2022, 11, 14))
+        );
+        assert_eq!(
+            working_days.previous_working_day(offset.ymd(2022, 11, 16)),
+            Ok(offset.ymd(2022, 11, 14))
+        );
+        assert_eq!(
+            working_days.previous_working_day(offset.ymd(2022, 11, 13)),
+            Ok(offset.ymd(2022, 11, 11))
+        );
+
+        assert_eq!(
+            working_days.add_working_days(offset.ymd(2022, 11, 12), 0),
+            Ok(offset.ymd(2022, 11, 12))
+        );
+        assert_eq!(
+            working_days.add_working_days(offset.ymd(2022, 11, 11), 2),
+            Ok(offset.ymd(2022, 11, 16))
+        );
+        assert_eq!(
+            working_days.add_working_days(offset.ymd(2022, 11, 30), 1),
+            Ok(offset.ymd(2022, 12, 1))
+        );
+        assert_eq!(
+            working_days.add_working_days(offset.ymd(2022, 11, 16), -2),
+            Ok(offset.ymd(2022, 11, 11))
+        );
+        assert_eq!(
+            working_days.add_working_days(offset.ymd(2022, 11, 15), -2),
+            Ok(offset.ymd(2022, 11, 11))
+        );
+
+        assert_eq!(
+            working_days.working_days_between(offset.ymd(2022, 11, 11), offset.ymd(2022, 11, 16)),
+            Ok(2)
+        );
+        assert_eq!(
+            working_days.working_days_between(offset.ymd(2022, 11, 16), offset.ymd(2022, 11, 11)),
+            Ok(-2)
+        );
+        assert_eq!(
+            working_days.working_days_between(offset.ymd(2022, 11, 1), offset.ymd(2022, 11, 1)),
+            Ok(0)
+        );
+    }
+
+    #[test]
+    fn should_return_error_if_arithmetic_crosses_table_bounds() {
+        let mut holidays = Vec::new();
+        let offset = FixedOffset::west(3 * 3600);
+
+        holidays.push(offset.ymd(2022, 11, 2).into());
+
+        let working_days = WorkingDays::build(offset, Weekend::default(), holidays).unwrap();
+        let out_of_range = DateOutOfRange(offset.ymd(2022, 1, 1), offset.ymd(2022, 12, 31));
+
+        assert_eq!(
+            working_days.is_working_day(offset.ymd(2023, 1, 2)),
+            Err(out_of_range.clone())
+        );
+        assert_eq!(
+            working_days.next_working_day(offset.ymd(2022, 12, 30)),
+            Err(out_of_range.clone())
+        );
+        assert_eq!(
+            working_days.previous_working_day(offset.ymd(2022, 1, 3)),
+            Err(out_of_range.clone())
+        );
+        assert_eq!(
+            working_days.add_working_days(offset.ymd(2022, 12, 1), 30),
+            Err(out_of_range.clone())
+        );
+        assert_eq!(
+            working_days.add_working_days(offset.ymd(2022, 1, 1), -1),
+            Err(out_of_range.clone())
+        );
+        assert_eq!(
+            working_days.working_days_between(offset.ymd(2022, 12, 1), offset.ymd(2023, 1, 2)),
+            Err(out_of_range)
+        );
+    }
+
+    #[test]
+    fn should_build_and_extend_with_rules() {
+        let offset = FixedOffset::west(3 * 3600);
+        let rules = vec![
+            HolidayRule::Fixed { month: 1, day: 3 }.into(),
+            HolidayRule::EasterOffset(60).into(),
+        ];
+
+        let mut working_days = WorkingDays::build_with_rules(
+            offset,
+            Weekend::default(),
+            vec![offset.ymd(2022, 11, 15).into()],
+            rules,
+            2023,
+            2023,
+        );
+
+        assert_eq!(working_days.start_date, offset.ymd(2022, 1, 1));
+        assert_eq!(working_days.end_date, offset.ymd(2023, 12, 31));
+        assert!(working_days.is_extendable());
+        assert!(!working_days
+            .is_working_day(offset.ymd(2022, 11, 15))
+            .unwrap());
+        assert!(!working_days
+            .is_working_day(offset.ymd(2022, 6, 16))
+            .unwrap());
+        assert!(!working_days.is_working_day(offset.ymd(2023, 1, 3)).unwrap());
+        assert!(!working_days.covers(offset.ymd(2025, 1, 3)));
+
+        working_days.extend_to(offset.ymd(2025, 1, 3)).unwrap();
+
+        assert_eq!(working_days.start_date, offset.ymd(2022, 1, 1));
+        assert_eq!(working_days.end_date, offset.ymd(2025, 12, 31));
+        assert!(!working_days
+            .is_working_day(offset.ymd(2022, 11, 15))
+            .unwrap());
+        assert!(!working_days
+            .is_working_day(offset.ymd(2024, 5, 30))
+            .unwrap());
+        assert!(!working_days.is_working_day(offset.ymd(2025, 1, 3)).unwrap());
+        assert!(working_days.is_working_day(offset.ymd(2025, 1, 2)).unwrap());
+
+        working_days.extend_to(offset.ymd(2020, 6, 1)).unwrap();
+        assert_eq!(working_days.start_date, offset.ymd(2020, 1, 1));
+        assert_eq!(working_days.working_days_mtd(offset.ymd(2020, 1, 6)), Ok(3));
+    }
+
+    #[test]
+    fn should_weight_partial_holidays() {
+        let offset = FixedOffset::west(3 * 3600);
+        let holidays = vec![
+            offset.ymd(2023, 2, 20).into(),
+            offset.ymd(2023, 2, 21).into(),
+            Holiday::new(offset.ymd(2023, 2, 22), Observance::None).with_weight(0.5),
+            Holiday::new(offset.ymd(2023, 2, 25), Observance::None).with_weight(0.5),
+        ];
+
+        let working_days = WorkingDays::build(offset, Weekend::default(), holidays).unwrap();
+
+        assert!(working_days
+            .is_working_day(offset.ymd(2023, 2, 22))
+            .unwrap());
+        assert_eq!(
+            working_days.working_days_mtd(offset.ymd(2023, 2, 22)),
+            Ok(14)
+        );
+        assert_eq!(
+            working_days.weighted_working_days_mtd(offset.ymd(2023, 2, 22)),
+            Ok(13.5)
+        );
+        assert_eq!(
+            working_days.weighted_working_days_mtd(offset.ymd(2023, 2, 25)),
+            Ok(15.5)
+        );
+        assert_eq!(
+            working_days.weighted_working_days_mtd(offset.ymd(2023, 3, 1)),
+            Ok(1.0)
+        );
+    }
+
+    #[test]
+    fn should_compose_calendars() {
+        let offset = FixedOffset::west(3 * 3600);
+        let br = WorkingDays::build(
+            offset,
+            Weekend::default(),
+            vec![
+                offset.ymd(2022, 11, 2).into(),
+                Holiday::new(offset.ymd(2022, 11, 3), Observance::None).with_weight(0.5),
+            ],
+        )
+        .unwrap();
+        let ae = WorkingDays::build(
+            FixedOffset::east(4 * 3600),
+            Weekend::new(&[Weekday::Fri, Weekday::Sat]),
+            vec![FixedOffset::east(4 * 3600).ymd(2022, 11, 30).into()],
+        )
+        .unwrap();
+
+        let union = WorkingDays::compose(Composition::Union, &[&br, &ae]).unwrap();
+        assert_eq!(union.time_offset, offset);
+        assert_eq!(
+            union.weekend,
+            Weekend::new(&[Weekday::Fri, Weekday::Sat, Weekday::Sun])
+        );
+        assert!(!union.is_working_day(offset.ymd(2022, 11, 2)).unwrap());
+        assert!(!union.is_working_day(offset.ymd(2022, 11, 30)).unwrap());
+        assert_eq!(union.working_weight(offset.ymd(2022, 11, 3)), Ok(0.5));
+        // Tuesday 1st, Thursday 3rd, Monday 7th
+        assert_eq!(union.working_days_mtd(offset.ymd(2022, 11, 7)), Ok(3));
+
+        let intersection = WorkingDays::compose(Composition::Intersection, &[&br, &ae]).unwrap();
+        assert_eq!(intersection.weekend, Weekend::new(&[Weekday::Sat]));
+        assert!(intersection
+            .is_working_day(offset.ymd(2022, 11, 2))
+            .unwrap());
+        assert!(intersection
+            .is_working_day(offset.ymd(2022, 11, 6))
+            .unwrap());
+        assert_eq!(
+            intersection.working_weight(offset.ymd(2022, 11, 3)),
+            Ok(1.0)
+        );
+        assert_eq!(
+            intersection.working_days_mtd(offset.ymd(2022, 11, 7)),
+            Ok(6)
+        );
+
+        let old = WorkingDays::build(
+            offset,
+            Weekend::default(),
+            vec![offset.ymd(2020, 1, 1).into()],
+        )
+        .unwrap();
+        assert_eq!(
+            WorkingDays::compose(Composition::Union, &[&br, &old]).unwrap_err(),
+            NoCommonRange
+        );
+        assert_eq!(
+            WorkingDays::compose(Composition::Union, &[]).unwrap_err(),
+            EmptyComposition
+        );
+    }
+
+    #[test]
+    fn should_keep_holiday_names() {
+        let offset = FixedOffset::west(3 * 3600);
+        let holidays = vec![
+            Holiday::from(offset.ymd(2022, 11, 15))
+                .with_name(Some("Proclamação da República".to_string())),
+            offset.ymd(2022, 11, 2).into(),
+        ];
+        let rules = vec![RecurringHoliday::new(
+            HolidayRule::Fixed { month: 12, day: 25 },
+            Observance::NextMonday,
+        )
+        .with_name(Some("Natal".to_string()))];
+
+        let working_days =
+            WorkingDays::build_with_rules(offset, Weekend::default(), holidays, rules, 2022, 2022);
+
+        assert_eq!(
+            working_days.holiday_name(offset.ymd(2022, 11, 15)),
+            Some("Proclamação da República")
+        );
+        assert_eq!(working_days.holiday_name(offset.ymd(2022, 11, 2)), None);
+        assert_eq!(
+            working_days.holiday_name(offset.ymd(2022, 12, 26)),
+            Some("Natal (observed)")
+        );
+        assert_eq!(working_days.holiday_name(offset.ymd(2022, 11, 16)), None);
+
+        let other = WorkingDays::build(
+            offset,
+            Weekend::default(),
+            vec![Holiday::from(offset.ymd(2022, 11, 15)).with_name(Some("Other".to_string()))],
+        )
+        .unwrap();
+        let union = WorkingDays::compose(Composition::Union, &[&working_days, &other]).unwrap();
+        assert_eq!(
+            union.holiday_name(offset.ymd(2022, 11, 15)),
+            Some("Proclamação da República, Other")
+        );
+    }
+
+    #[test]
+    fn should_count_make_up_working_days() {
+        let offset = FixedOffset::west(3 * 3600);
+        let holidays = vec![
+            Holiday::workday(offset.ymd(2022, 10, 8)),
+            offset.ymd(2022, 10, 12).into(),
+        ];
+
+        let working_days = WorkingDays::build(offset, Weekend::default(), holidays).unwrap();
+
+        assert!(working_days
+            .is_working_day(offset.ymd(2022, 10, 8))
+            .unwrap());
+        assert_eq!(
+            working_days.working_days_mtd(offset.ymd(2022, 10, 8)),
+            Ok(6)
+        );
+        assert_eq!(
+            working_days.working_days_mtd(offset.ymd(2022, 10, 14)),
+            Ok(10)
+        );
+        assert_eq!(
+            working_days.next_working_day(offset.ymd(2022, 10, 7)),
+            Ok(offset.ymd(2022, 10, 8))
+        );
+    }
+
+    #[test]
+    fn should_apply_holiday_observance() {
+        let offset = FixedOffset::west(3 * 3600);
+        let holidays = vec![
+            Holiday::new(offset.ymd(2021, 12, 25), Observance::NextMonday),
+            Holiday::new(offset.ymd(2021, 12, 26), Observance::NextMonday),
+            Holiday::new(offset.ymd(2022, 1, 1), Observance::NearestWeekday),
+            Holiday::new(offset.ymd(2022, 6, 19), Observance::None),
+        ];
+
+        let working_days = WorkingDays::build(offset, Weekend::default(), holidays).unwrap();
+
+        assert!(!working_days
+            .is_working_day(offset.ymd(2021, 12, 27))
+            .unwrap());
+        assert!(!working_days
+            .is_working_day(offset.ymd(2021, 12, 28))
+            .unwrap());
+        assert!(!working_days
+            .is_working_day(offset.ymd(2021, 12, 31))
+            .unwrap());
+        assert!(working_days
+            .is_working_day(offset.ymd(2022, 6, 20))
+            .unwrap());
+        assert_eq!(
+            working_days.working_days_mtd(offset.ymd(2021, 12, 31)),
+            Ok(20)
+        );
+
+        let rules = vec![RecurringHoliday::new(
+            HolidayRule::Fixed { month: 7, day: 4 },
+            Observance::NearestWeekday,
+        )];
+        let working_days = WorkingDays::build_with_rules(
+            offset,
+            Weekend::default(),
+            Vec::new(),
+            rules,
+            2020,
+            2021,
+        );
+
+        // 2020-07-04 is a Saturday and 2021-07-04 a Sunday
+        assert!(!working_days.is_working_day(offset.ymd(2020, 7, 3)).unwrap());
+        assert!(!working_days.is_working_day(offset.ymd(2021, 7, 5)).unwrap());
+    }
+
+    #[test]
+    fn should_not_extend_without_rules() {
+        let offset = FixedOffset::west(3 * 3600);
+
+        let mut working_days = WorkingDays::build(
+            offset,
+            Weekend::default(),
+            vec![offset.ymd(2022, 11, 15).into()],
+        )
+        .unwrap();
+
+        assert!(!working_days.is_extendable());
+        assert_eq!(
+            working_days.extend_to(offset.ymd(2023, 1, 1)),
+            Err(DateOutOfRange(
+                offset.ymd(2022, 1, 1),
+                offset.ymd(2022, 12, 31)
+            ))
+        );
+    }
+
+    #[test]
+    fn should_parse_and_display_weekend() {
+        assert_eq!(Weekend::from_str("sat,sun").unwrap(), Weekend::default());
+        assert_eq!(
+            Weekend::from_str("Friday, Saturday").unwrap(),
+            Weekend::new(&[Weekday::Fri, Weekday::Sat])
+        );
+        assert_eq!(Weekend::from_str("none").unwrap(), Weekend::new(&[]));
+        assert_eq!(
+            Weekend::from_str("sat,jose").unwrap_err(),
+            InvalidWeekend("sat,jose".to_string())
+        );
+
+        assert_eq!(Weekend::default().to_string(), "sat,sun");
+        assert_eq!(Weekend::new(&[Weekday::Sun]).to_string(), "sun");
+        assert_eq!(Weekend::new(&[]).to_string(), "none");
+    }
+
+    #[test]
+    fn should_calculate_working_days_with_custom_weekend() {
+        let mut holidays = Vec::new();
+        let offset = FixedOffset::west(3 * 3600);
+
+        holidays.push(offset.ymd(2022, 6, 16).into());
+
+        let weekend = Weekend::new(&[Weekday::Fri, Weekday::Sat]);
+        let working_days = WorkingDays::build(offset, weekend, holidays).unwrap();
+
+        let june: Vec<u8> = vec![
+            1, 2, 2, 2, 3, 4, 5, 6, 7, 7, 7, 8, 9, 10, 11, 11, 11, 11, 12, 13, 14, 15, 16, 16, 16,
+            17, 18, 19, 20, 21,
+        ];
+        let mut current_date = offset.ymd(2022, 6, 1);
+        for wds in june {
+            assert_eq!(working_days.working_days_mtd(current_date).unwrap(), wds);
+            current_date += Duration::days(1);
+        }
+
+        let six_days_week = WorkingDays::build(
+            offset,
+            Weekend::new(&[Weekday::Sun]),
+            vec![offset.ymd(2022, 6, 16).into()],
+        )
+        .unwrap();
+
+        assert_eq!(
+            six_days_week
+                .working_days_mtd(offset.ymd(2022, 6, 11))
+                .unwrap(),
+            10
+        );
+    }
 }